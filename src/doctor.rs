@@ -0,0 +1,151 @@
+use crate::{
+    log_error,
+    opt::{Cdn, Opt, Quality},
+    stream::{Game, GameState, LazyStream, Stream},
+};
+use async_std::{process, task};
+use colored::Colorize;
+use failure::{bail, Error};
+use std::time::Instant;
+
+const CDNS: [Cdn; 2] = [Cdn::Akc, Cdn::L3c];
+
+const QUALITIES: [Quality; 10] = [
+    Quality::Best,
+    Quality::Worst,
+    Quality::_720p60,
+    Quality::_720p,
+    Quality::_540p,
+    Quality::_504p,
+    Quality::_360p,
+    Quality::_288p,
+    Quality::_224p,
+    Quality::_216p,
+];
+
+pub fn run(opts: Opt) {
+    task::block_on(async {
+        if let Err(e) = process(opts).await {
+            log_error(&e);
+            process::exit(1);
+        };
+    });
+}
+
+async fn process(mut opts: Opt) -> Result<(), Error> {
+    // Each CDN and quality is probed on its own, without falling back to another
+    opts.no_fallback = true;
+    opts.quality_fallback = false;
+    opts.max_quality = None;
+
+    println!("Getting schedule...");
+    let lazy_stream = LazyStream::new(&opts).await?;
+
+    let (game, stream) = match first_stream(lazy_stream.games()).await {
+        Some(found) => found,
+        None => bail!(
+            "No games with streams found for {}",
+            lazy_stream.date().format("%Y-%m-%d")
+        ),
+    };
+
+    println!(
+        "Testing {} @ {} {}...\n",
+        game.away_team.name, game.home_team.name, stream.feed_type
+    );
+
+    let mut columns = vec![];
+    for cdn in CDNS.iter() {
+        columns.push(probe_cdn(&stream, cdn).await);
+    }
+
+    print!("{:<10}", "QUALITY");
+    for cdn in CDNS.iter() {
+        print!("{:<16}", cdn);
+    }
+    println!();
+
+    let labels = std::iter::once("master").chain(QUALITIES.iter().map(Quality::arg));
+    for (row, label) in labels.enumerate() {
+        print!("{:<10}", label);
+        for column in columns.iter() {
+            print!("{}", cell(column[row]));
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// First stream of a game, preferring live games since their streams are most
+/// likely to be up
+async fn first_stream(mut games: Vec<Game>) -> Option<(Game, Stream)> {
+    games.sort_by_key(|game| game.status.state() != GameState::Live);
+
+    for mut game in games {
+        let mut streams: Vec<Stream> = match game.streams().await {
+            Ok(streams) => streams.into_iter().map(|(_, stream)| stream).collect(),
+            Err(_) => continue,
+        };
+        streams.sort_by(|a, b| a.feed_type.cmp(&b.feed_type));
+
+        if !streams.is_empty() {
+            return Some((game, streams.remove(0)));
+        }
+    }
+
+    None
+}
+
+/// Milliseconds taken to resolve and load the master link, then each quality link,
+/// or None where it failed
+async fn probe_cdn(stream: &Stream, cdn: &Cdn) -> Vec<Option<u128>> {
+    println!("Probing {}...", cdn);
+
+    let mut stream = stream.clone();
+    let mut results = vec![];
+
+    let start = Instant::now();
+    let master_ok = match stream.master_link(cdn).await {
+        Ok(link) => stream.verify_link(&link).await,
+        Err(_) => false,
+    };
+    results.push(if master_ok {
+        Some(start.elapsed().as_millis())
+    } else {
+        None
+    });
+
+    // Fetch the master m3u8 once, so each quality only needs its own link checked
+    let master_ok = master_ok && stream.variants(cdn).await.is_ok();
+
+    for quality in QUALITIES.iter() {
+        if !master_ok {
+            results.push(None);
+            continue;
+        }
+
+        let mut stream = stream.clone();
+        let start = Instant::now();
+        let quality_ok = match stream.quality_link(cdn, quality).await {
+            Ok(link) => stream.verify_link(&link).await,
+            Err(_) => false,
+        };
+        results.push(if quality_ok {
+            Some(start.elapsed().as_millis())
+        } else {
+            None
+        });
+    }
+
+    results
+}
+
+fn cell(result: Option<u128>) -> String {
+    match result {
+        Some(millis) => format!("{:<16}", format!("PASS {}ms", millis))
+            .green()
+            .to_string(),
+        None => format!("{:<16}", "FAIL").red().to_string(),
+    }
+}
@@ -1,13 +1,29 @@
 use crate::{
-    log_error,
-    opt::{Cdn, Command, GenerateCommand, Opt, Quality},
-    stream::{Game, LazyStream},
+    format_time, log_error,
+    opt::{
+        Command, FeedType, GenerateCommand, GenerateOptions, LineEnding, Opt, OutputFormat,
+        PlaylistFormat, SplitBy, TitleTemplate, XmltvDialect, XmltvOptions, DEFAULT_TITLE_TEMPLATE,
+    },
+    sanitize_filename, status,
+    stream::{self, Game, GameState, LazyStream, Stream},
     VERSION,
 };
 use async_std::{fs, process, task};
-use chrono::Local;
-use failure::Error;
-use std::path::PathBuf;
+use chrono::{Duration, Local};
+use failure::{bail, format_err, Error};
+use log::warn;
+use stats_api::model::nhl::Team;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Playlist file name to write to stdout instead
+const STDOUT_FILE: &str = "-";
+
+/// Channel icon used when there's no game thumbnail
+const NHL_LOGO: &str = "https://www-league.nhlstatic.com/images/logos/league-dark/133-flat.svg";
 
 pub fn run(opts: Opt) {
     task::block_on(async {
@@ -18,51 +34,152 @@ pub fn run(opts: Opt) {
     });
 }
 
-async fn process(opts: Opt) -> Result<(), Error> {
-    if let Command::Generate { command } = &opts.command {
+pub async fn process(opts: Opt) -> Result<(), Error> {
+    let to_stdout = match &opts.command {
+        Command::Generate {
+            command: GenerateCommand::Playlist { file, .. },
+            ..
+        } => file == Path::new(STDOUT_FILE),
+        _ => false,
+    };
+
+    if let Command::Generate { command, .. } = &opts.command {
         match command {
-            GenerateCommand::Xmltv { .. } => {
-                println!("Creating .m3u & .xml for XMLTV...");
+            GenerateCommand::Xmltv { .. } | GenerateCommand::Favorites { .. } => {
+                status(to_stdout, "Creating .m3u & .xml for XMLTV...");
+            }
+            GenerateCommand::Strm { .. } => status(to_stdout, "Creating .strm files..."),
+            GenerateCommand::Files { formats, .. } => {
+                let formats: Vec<String> = formats
+                    .iter()
+                    .map(|format| format!(".{}", format))
+                    .collect();
+                status(to_stdout, &format!("Creating {}...", formats.join(" & ")));
             }
-            _ => println!("Creating playlist file..."),
+            _ => status(to_stdout, "Creating playlist file..."),
         }
     }
 
-    let mut lazy_stream = LazyStream::new(&opts).await?;
+    let dry_run = match &opts.command {
+        Command::Generate { options, .. } => options.dry_run,
+        _ => false,
+    };
 
-    if let Some(quality) = &opts.quality {
-        lazy_stream
-            .resolve_with_quality_link(&opts.cdn, quality)
-            .await;
-    } else {
-        lazy_stream.resolve_with_master_link(&opts.cdn).await;
+    let show_progress = match &opts.command {
+        Command::Generate { options, .. } => !options.quiet && atty::is(atty::Stream::Stderr),
+        _ => false,
+    };
+
+    let mut games = games(&opts, show_progress).await?;
+
+    if let Command::Generate { options, .. } = &opts.command {
+        if !options.no_dedupe {
+            let merged = dedupe_streams(&mut games, &opts).await;
+            if merged > 0 {
+                status(
+                    to_stdout,
+                    &format!("Merged {} streams with the same link as another", merged),
+                );
+            }
+        }
+
+        if options.verify && !dry_run {
+            status(to_stdout, "Verifying links...");
+            let dropped = verify_links(&mut games, &opts).await;
+            status(
+                to_stdout,
+                &format!("Dropped {} streams with links that failed to load", dropped),
+            );
+        }
+    }
+
+    let fallback_count = games
+        .iter()
+        .filter_map(|game| game.streams.as_ref())
+        .flat_map(|streams| streams.values())
+        .filter(|stream| stream.cdn().map_or(false, |cdn| cdn != &opts.cdn))
+        .count();
+    if fallback_count > 0 {
+        let msg = format!(
+            "{} streams not available on {}, using {} instead",
+            fallback_count,
+            opts.cdn,
+            opts.cdn.fallback()
+        );
+        status(to_stdout, &msg);
     }
 
-    let games = lazy_stream.games();
+    let host = opts.hosts().remove(0);
+    let backup_host_count = games
+        .iter()
+        .filter_map(|game| game.streams.as_ref())
+        .flat_map(|streams| streams.values())
+        .filter(|stream| stream.host().map_or(false, |resolved| resolved != host))
+        .count();
+    if backup_host_count > 0 {
+        status(
+            to_stdout,
+            &format!(
+                "{} streams not available from {}, using another host instead",
+                backup_host_count, host
+            ),
+        );
+    }
+
+    if let Command::Generate { options, command } = &opts.command {
+        let out_dir = options
+            .out_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("."));
 
-    if let Command::Generate { command } = opts.command {
         match command {
-            GenerateCommand::Xmltv {
+            GenerateCommand::Xmltv { file, xmltv }
+            | GenerateCommand::Favorites { file, xmltv, .. } => {
+                let path = output_path(&out_dir, file).await?;
+                create_guide(&path, games, &opts, xmltv, true, true).await?;
+            }
+            GenerateCommand::Playlist {
                 file,
-                start_channel,
+                group_by_game,
+                split_by: Some(SplitBy::Feed),
+                continue_numbering,
             } => {
-                let path = file.with_extension("m3u");
-                create_playlist(
-                    path.clone(),
-                    games.clone(),
-                    &opts.cdn,
-                    &opts.quality,
-                    true,
-                    start_channel,
-                )
-                .await?;
-
-                let path = path.with_extension("xml");
-                create_xmltv(path, games, &opts.cdn, &opts.quality, start_channel).await?;
+                if to_stdout {
+                    bail!("Split playlists can't be written to stdout, pass a file name instead");
+                }
+                let path = output_path(&out_dir, file).await?;
+                create_feed_playlists(&path, games, &opts, *group_by_game, *continue_numbering)
+                    .await?;
             }
-            GenerateCommand::Playlist { file } => {
-                let path = file.with_extension("m3u");
-                create_playlist(path, games, &opts.cdn, &opts.quality, false, 1000).await?;
+            GenerateCommand::Playlist {
+                file,
+                group_by_game,
+                ..
+            } => {
+                let path = if to_stdout {
+                    file.clone()
+                } else {
+                    default_extension(output_path(&out_dir, file).await?, "m3u")
+                };
+                create_playlist(path, games, &opts, false, 1000, *group_by_game).await?;
+            }
+            GenerateCommand::Strm { dir, nfo } => {
+                create_strm(&out_dir.join(dir), games, &opts, *nfo).await?;
+            }
+            GenerateCommand::Files {
+                name,
+                formats,
+                nfo,
+                xmltv,
+            } => {
+                let path = output_path(&out_dir, name).await?;
+                if formats.contains(&OutputFormat::Strm) {
+                    create_strm(&path, games.clone(), &opts, *nfo).await?;
+                }
+
+                let m3u = formats.contains(&OutputFormat::M3u);
+                let xml = formats.contains(&OutputFormat::Xml);
+                create_guide(&path, games, &opts, xmltv, m3u, xml).await?;
             }
         }
     }
@@ -70,70 +187,713 @@ async fn process(opts: Opt) -> Result<(), Error> {
     Ok(())
 }
 
+/// Get the games for the date, date range or rolling --days window, with their streams resolved unless it's a dry
+/// run. Games are sorted by start time, and filtered by --limit and --feeds.
+pub async fn games(opts: &Opt, show_progress: bool) -> Result<Vec<Game>, Error> {
+    let dry_run = match &opts.command {
+        Command::Generate { options, .. } => options.dry_run,
+        _ => false,
+    };
+
+    let dates = match &opts.command {
+        Command::Generate {
+            options:
+                GenerateOptions {
+                    date_range: Some(date_range),
+                    ..
+                },
+            ..
+        } => date_range.dates().into_iter().map(Some).collect(),
+        Command::Generate {
+            options: GenerateOptions {
+                days: Some(days), ..
+            },
+            ..
+        } => {
+            let today = Local::today().naive_local();
+            if today
+                .checked_add_signed(Duration::days(i64::from(*days)))
+                .is_none()
+            {
+                bail!("--days {} is too large", days);
+            }
+            (0..=i64::from(*days))
+                .map(|offset| Some(today + Duration::days(offset)))
+                .collect()
+        }
+        _ => vec![opts.date],
+    };
+
+    let favorites = match &opts.command {
+        Command::Generate {
+            command: GenerateCommand::Favorites { teams, .. },
+            ..
+        } => {
+            if teams.is_empty() {
+                bail!(
+                    "No favorite teams, pass them after FILE or list them as favorites in \
+                     the config file"
+                );
+            }
+            Some(teams)
+        }
+        _ => None,
+    };
+
+    let (limit, national_only) = match &opts.command {
+        Command::Generate { options, .. } => (options.limit, options.national_only),
+        _ => (None, false),
+    };
+
+    // One client for every date, so connections are reused
+    let client = stream::Client::new(opts);
+
+    let mut games = vec![];
+    for date in dates {
+        if limit.map_or(false, |limit| games.len() >= limit) {
+            break;
+        }
+
+        let mut date_opts = opts.clone();
+        date_opts.date = date;
+
+        let mut lazy_stream = LazyStream::with_client(&date_opts, client.clone()).await?;
+        // Filtered before resolving, so other games' links aren't fetched
+        if let Some(teams) = favorites {
+            lazy_stream.retain_teams(teams);
+        }
+        if national_only {
+            lazy_stream.retain_national();
+        }
+        // Only the streams are loaded, so links aren't resolved for other feeds, and games
+        // left without any are dropped before the limit
+        if !opts.feeds.is_empty() {
+            lazy_stream
+                .retain_feeds(&opts.feeds, opts.concurrency)
+                .await;
+        }
+        // Dates are in order, so the earliest games left under the limit are all from
+        // this date. Truncating before resolving skips fetching the links of the rest
+        if let Some(limit) = limit {
+            lazy_stream.truncate(limit.saturating_sub(games.len()));
+        }
+        if show_progress {
+            lazy_stream.on_progress(print_progress);
+        }
+
+        if dry_run {
+            lazy_stream.resolve_streams(opts.concurrency).await;
+        } else if let Some(quality) = &opts.quality {
+            lazy_stream
+                .resolve_with_quality_link(&opts.cdn, quality, opts.concurrency)
+                .await;
+        } else {
+            lazy_stream
+                .resolve_with_master_link(&opts.cdn, opts.concurrency)
+                .await;
+        }
+
+        games.extend(lazy_stream.games());
+    }
+    games.sort_by_key(|game| game.game_date);
+
+    Ok(games)
+}
+
+/// Path of an output file in the output directory, creating any missing directories
+async fn output_path(out_dir: &Path, file: &Path) -> Result<PathBuf, Error> {
+    let path = out_dir.join(file);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    Ok(path)
+}
+
+/// Set the extension if the path doesn't already have one, so ie. '.m3u8' is kept
+fn default_extension(path: PathBuf, extension: &str) -> PathBuf {
+    if path.extension().is_some() {
+        path
+    } else {
+        path.with_extension(extension)
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Merge any streams with the same link as another stream of the game into it, labeled
+/// with both feeds, returning how many were merged. Feed types are checked in order, so
+/// ie. AWAY is merged into HOME, labeled 'HOME/AWAY'.
+async fn dedupe_streams(games: &mut [Game], opts: &Opt) -> usize {
+    let mut merged = 0;
+    for game in games.iter_mut() {
+        if let Some(streams) = game.streams.as_mut() {
+            let mut feed_types: Vec<FeedType> = streams.keys().cloned().collect();
+            feed_types.sort();
+
+            let mut links: HashMap<String, FeedType> = HashMap::new();
+            for feed_type in feed_types {
+                let link = match streams.get_mut(&feed_type) {
+                    Some(stream) => stream_link(stream, opts).await,
+                    None => continue,
+                };
+                if let Ok(link) = link {
+                    match links.get(&link) {
+                        Some(kept) => {
+                            streams.remove(&feed_type);
+                            if let Some(stream) = streams.get_mut(kept) {
+                                stream.merge_feed(feed_type);
+                            }
+                            merged += 1;
+                        }
+                        None => {
+                            links.insert(link, feed_type);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    merged
+}
+
+/// Drop any streams with a link that doesn't load, returning how many were dropped
+async fn verify_links(games: &mut [Game], opts: &Opt) -> usize {
+    let mut dropped = 0;
+    for game in games.iter_mut() {
+        if let Some(streams) = game.streams.as_mut() {
+            let mut failed = vec![];
+
+            for (feed_type, stream) in streams.iter_mut() {
+                if let Ok(link) = stream_link(stream, opts).await {
+                    if !stream.verify_link(&link).await {
+                        failed.push(feed_type.clone());
+                    }
+                }
+            }
+
+            dropped += failed.len();
+            for feed_type in failed {
+                streams.remove(&feed_type);
+            }
+        }
+    }
+
+    dropped
+}
+
+/// Link to add for the stream. For a dry run, the host link is used as a placeholder
+/// so no links need to be resolved.
+async fn stream_link(stream: &mut Stream, opts: &Opt) -> Result<String, Error> {
+    if let Command::Generate { options, .. } = &opts.command {
+        if options.dry_run {
+            return Ok(stream.host_link(&opts.cdn));
+        }
+    }
+
+    if let Some(quality) = &opts.quality {
+        stream.quality_link(&opts.cdn, quality).await
+    } else {
+        stream.master_link(&opts.cdn).await
+    }
+}
+
+/// Link to add for a stream of the game. With --days, streams of upcoming games that
+/// aren't available yet get the unresolved host link as a placeholder instead, since the
+/// host resolves it once the stream is live.
+async fn entry_link(stream: &mut Stream, upcoming: bool, opts: &Opt) -> Result<String, Error> {
+    let link = stream_link(stream, opts).await;
+    if link.is_err() && upcoming && rolling(opts) {
+        return Ok(stream.host_link(&opts.cdn));
+    }
+    link
+}
+
+/// Whether --days was used, building a guide of upcoming games
+fn rolling(opts: &Opt) -> bool {
+    match &opts.command {
+        Command::Generate { options, .. } => options.days.is_some(),
+        _ => false,
+    }
+}
+
+/// Show how many games have been resolved, on one line that's overwritten
+fn print_progress(count: usize, total: usize) {
+    eprint!("\rResolving links {}/{}", count, total);
+    if count == total {
+        eprintln!();
+    }
+}
+
 async fn create_playlist(
     path: PathBuf,
     mut games: Vec<Game>,
-    cdn: &Cdn,
-    quality: &Option<Quality>,
+    opts: &Opt,
     is_xmltv: bool,
     start_channel: u32,
+    group_by_game: bool,
 ) -> Result<(), Error> {
+    let m3u = playlist(&mut games, opts, is_xmltv, start_channel, group_by_game).await?;
+    let m3u = encode(m3u, opts);
+
+    if path == Path::new(STDOUT_FILE) {
+        io::stdout().write_all(&m3u)?;
+        return Ok(());
+    }
+
+    fs::write(&path, m3u).await?;
+
+    println!("Playlist saved to: {:?}", path);
+
+    Ok(())
+}
+
+/// Write a playlist for each feed type, named after path with the feed type appended
+async fn create_feed_playlists(
+    path: &Path,
+    games: Vec<Game>,
+    opts: &Opt,
+    group_by_game: bool,
+    continue_numbering: bool,
+) -> Result<(), Error> {
+    let mut feed_types: Vec<FeedType> = games
+        .iter()
+        .filter_map(|game| game.streams.as_ref())
+        .flat_map(|streams| streams.keys().cloned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    feed_types.sort();
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("m3u"));
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut start_channel = 1000;
+    for feed_type in feed_types {
+        let mut feed_games = games.clone();
+        for game in feed_games.iter_mut() {
+            if let Some(streams) = game.streams.as_mut() {
+                streams.retain(|stream_feed_type, _| stream_feed_type == &feed_type);
+            }
+        }
+
+        let m3u = playlist(&mut feed_games, opts, false, start_channel, group_by_game).await?;
+        if continue_numbering {
+            let count = m3u.matches("#EXTINF").count() as u32;
+            start_channel = channel_number(start_channel, count, channel_step(opts))?;
+        }
+
+        let file = format!(
+            "{}-{}.{}",
+            stem,
+            feed_type.to_string().to_lowercase(),
+            extension
+        );
+        let feed_path = path.with_file_name(file);
+        fs::write(&feed_path, encode(m3u, opts)).await?;
+
+        println!("Playlist saved to: {:?}", feed_path);
+    }
+
+    Ok(())
+}
+
+/// Build the .m3u playlist of every stream with a link
+pub async fn playlist(
+    games: &mut [Game],
+    opts: &Opt,
+    is_xmltv: bool,
+    start_channel: u32,
+    group_by_game: bool,
+) -> Result<String, Error> {
     let mut m3u = String::new();
     m3u.push_str("#EXTM3U\n");
 
+    let (format, template) = match &opts.command {
+        Command::Generate { options, .. } => (
+            options.playlist_format.clone(),
+            options.title_template.clone(),
+        ),
+        _ => (
+            PlaylistFormat::Default,
+            DEFAULT_TITLE_TEMPLATE.parse::<TitleTemplate>().unwrap(),
+        ),
+    };
+    let step = channel_step(opts);
+
     let mut id: u32 = 0;
     for game in games.iter_mut() {
-        for (_, stream) in game.streams.as_mut().unwrap().iter_mut() {
-            let link = if let Some(quality) = quality {
-                stream.quality_link(cdn, quality).await
-            } else {
-                stream.master_link(cdn).await
-            };
+        let game_logo = game
+            .game_cuts()
+            .await
+            .map(|game_cuts| game_cuts.cut_320_180.src);
+        let upcoming = game.status.state() == GameState::Preview;
+
+        let mut streams = match game.streams.as_mut() {
+            Some(streams) => streams.iter_mut().collect::<Vec<_>>(),
+            None => {
+                warn!("Skipping game {}, its streams failed to load", game.game_pk);
+                continue;
+            }
+        };
+        // Keep a game's feeds in a consistent order within its group
+        if group_by_game {
+            streams.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+
+        for (_, stream) in streams {
+            let link = entry_link(stream, upcoming, opts).await;
 
             if let Ok(link) = link {
                 let title = if is_xmltv {
                     format!("Lazyman {}", id + 1)
                 } else {
+                    template.render(&[
+                        (
+                            "time",
+                            format_time(game.game_date, opts.timezone, "%-I:%M %p"),
+                        ),
+                        (
+                            "date",
+                            format_time(game.game_date, opts.timezone, "%Y-%m-%d"),
+                        ),
+                        ("away", game.away_team.team_name.clone()),
+                        ("home", game.home_team.team_name.clone()),
+                        ("feed", stream.label()),
+                        ("sport", String::from("NHL")),
+                    ])
+                };
+                let logo = match stream.feed_type {
+                    FeedType::Home => Some(team_logo(&game.home_team)),
+                    FeedType::Away => Some(team_logo(&game.away_team)),
+                    _ => game_logo.clone(),
+                };
+
+                let group = if group_by_game {
                     format!(
-                        "{} {} @ {} {}",
-                        game.game_date
-                            .with_timezone(&Local)
-                            .time()
-                            .format("%-I:%M %p")
-                            .to_string(),
-                        game.away_team.team_name,
-                        game.home_team.team_name,
-                        stream.feed_type,
+                        "{} @ {}",
+                        game.away_team.team_name, game.home_team.team_name
+                    )
+                } else {
+                    format!(
+                        "NHL {}",
+                        format_time(game.game_date, opts.timezone, "%Y-%m-%d")
                     )
                 };
-                let record = format!(
-                    "#EXTINF:-1 CUID=\"{}\" tvg-id=\"{}\" tvg-name=\"Lazyman {}\",{}\n{}\n",
-                    start_channel + id,
-                    start_channel + id,
-                    id + 1,
-                    title,
-                    link
-                );
+
+                let language = match stream.feed_type {
+                    FeedType::French => "French",
+                    _ => "English",
+                };
+
+                // Tvheadend, Kodi and Plex (through xTeVe) number channels with tvg-chno
+                let number = if format == PlaylistFormat::Default {
+                    "CUID"
+                } else {
+                    "tvg-chno"
+                };
+                let channel = channel_number(start_channel, id, step)?.to_string();
+                let mut attributes = vec![(number, channel.clone()), ("tvg-id", channel)];
+                attributes.push(("tvg-name", format!("Lazyman {}", id + 1)));
+                if format == PlaylistFormat::Default || format == PlaylistFormat::Kodi {
+                    attributes.push(("tvg-language", language.to_string()));
+                }
+                attributes.push(("group-title", group));
+                if let Some(logo) = logo {
+                    attributes.push(("tvg-logo", logo));
+                }
+                // Tvheadend and Plex don't have radio channels
+                if opts.audio
+                    && format != PlaylistFormat::Tvheadend
+                    && format != PlaylistFormat::Plex
+                {
+                    attributes.push(("radio", "true".to_string()));
+                }
+
+                let attributes = attributes
+                    .into_iter()
+                    .map(|(key, value)| format!(" {}=\"{}\"", key, value))
+                    .collect::<String>();
+                let record = format!("#EXTINF:-1{},{}\n{}\n", attributes, title, link);
                 m3u.push_str(&record);
                 id += 1;
             }
         }
     }
 
-    fs::write(&path, m3u).await?;
+    Ok(m3u)
+}
 
-    println!("Playlist saved to: {:?}", path);
+/// Write a .strm file containing the link for each stream, with an optional .nfo file
+async fn create_strm(dir: &Path, mut games: Vec<Game>, opts: &Opt, nfo: bool) -> Result<(), Error> {
+    fs::create_dir_all(dir).await?;
+
+    let mut count = 0;
+    for game in games.iter_mut() {
+        let description = game.description().await.unwrap_or_default();
+        let thumb = game
+            .game_cuts()
+            .await
+            .map(|game_cuts| game_cuts.cut_2048_1152.src);
+
+        let streams = match game.streams.as_mut() {
+            Some(streams) => streams,
+            None => {
+                warn!("Skipping game {}, its streams failed to load", game.game_pk);
+                continue;
+            }
+        };
+        for (_, stream) in streams.iter_mut() {
+            if let Ok(link) = stream_link(stream, opts).await {
+                // Team names can contain a '.', so extensions are appended rather than set
+                let name = sanitize_filename(&format!(
+                    "{} {} @ {} {}",
+                    format_time(game.game_date, opts.timezone, "%Y-%m-%d"),
+                    game.away_team.name,
+                    game.home_team.name,
+                    stream.label(),
+                ));
+                fs::write(dir.join(format!("{}.strm", name)), link).await?;
+
+                if nfo {
+                    let title = format!(
+                        "{} @ {} {}",
+                        game.away_team.name,
+                        game.home_team.name,
+                        stream.label()
+                    );
+                    let nfo = create_nfo(game, &title, &description, &thumb, opts);
+                    fs::write(dir.join(format!("{}.nfo", name)), nfo).await?;
+                }
+                count += 1;
+            }
+        }
+    }
+
+    println!("{} .strm files saved to: {:?}", count, dir);
 
     Ok(())
 }
 
+/// Kodi movie .nfo metadata for the game
+fn create_nfo(
+    game: &Game,
+    title: &str,
+    description: &str,
+    thumb: &Option<String>,
+    opts: &Opt,
+) -> String {
+    let thumb = thumb
+        .as_ref()
+        .map(|thumb| {
+            format!(
+                "\n  <thumb aspect=\"landscape\">{}</thumb>",
+                xml_escape(thumb)
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+         \n<movie>\
+         \n  <title>{}</title>\
+         \n  <plot>{}</plot>\
+         \n  <premiered>{}</premiered>\
+         \n  <genre>Hockey</genre>\
+         \n  <studio>NHL</studio>\
+         \n  <tag>{}</tag>\
+         \n  <tag>{}</tag>\
+         {}\
+         \n</movie>\n",
+        xml_escape(title),
+        xml_escape(description),
+        format_time(game.game_date, opts.timezone, "%Y-%m-%d"),
+        xml_escape(&game.away_team.name),
+        xml_escape(&game.home_team.name),
+        thumb,
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Bytes to write for the output, with the --line-endings and --bom options applied
+fn encode(text: String, opts: &Opt) -> Vec<u8> {
+    let (line_ending, bom) = match &opts.command {
+        Command::Generate { options, .. } => (options.line_endings.clone(), options.bom),
+        _ => (LineEnding::Lf, false),
+    };
+
+    let text = match line_ending {
+        LineEnding::Lf => text,
+        LineEnding::Crlf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+    };
+
+    let mut bytes = vec![];
+    if bom {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    bytes.extend_from_slice(text.as_bytes());
+    bytes
+}
+
+/// Gap between channel numbers, so the playlist and xmltv number channels the same way
+fn channel_step(opts: &Opt) -> u32 {
+    match &opts.command {
+        Command::Generate { options, .. } => options.channel_step,
+        _ => 1,
+    }
+}
+
+/// Number of the channel at index, erroring instead of overflowing with a large
+/// --start-channel or --channel-step
+fn channel_number(start_channel: u32, index: u32, step: u32) -> Result<u32, Error> {
+    index
+        .checked_mul(step)
+        .and_then(|offset| start_channel.checked_add(offset))
+        .ok_or_else(|| {
+            format_err!("Channel numbers are too large, lower --start-channel or --channel-step")
+        })
+}
+
+/// Logo image of the team from the NHL's static content
+fn team_logo(team: &Team) -> String {
+    format!(
+        "https://www-league.nhlstatic.com/images/logos/teams-current-primary-light/{}.svg",
+        team.id
+    )
+}
+
+/// Write the .m3u playlist and / or .xml guide next to each other at path. When both are
+/// written, playlist entries are titled to match the guide's channels.
+async fn create_guide(
+    path: &Path,
+    games: Vec<Game>,
+    opts: &Opt,
+    xmltv: &XmltvOptions,
+    m3u: bool,
+    xml: bool,
+) -> Result<(), Error> {
+    if m3u {
+        // An .xml extension is for the guide, so the playlist gets the default instead
+        let path = if has_extension(path, "xml") {
+            path.with_extension("m3u")
+        } else {
+            default_extension(path.to_path_buf(), "m3u")
+        };
+        create_playlist(path, games.clone(), opts, xml, xmltv.start_channel, false).await?;
+    }
+
+    if xml {
+        let duration =
+            xmltv.pre_pad + Duration::minutes(i64::from(xmltv.duration)) + xmltv.post_pad;
+        create_xmltv(
+            path.with_extension("xml"),
+            games,
+            opts,
+            xmltv.start_channel,
+            xmltv.pre_pad,
+            duration,
+            &xmltv.channel_icon,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Xmltv options of the xmltv or files subcommand
+fn xmltv_options(opts: &Opt) -> Option<&XmltvOptions> {
+    match &opts.command {
+        Command::Generate {
+            command: GenerateCommand::Xmltv { xmltv, .. },
+            ..
+        }
+        | Command::Generate {
+            command: GenerateCommand::Files { xmltv, .. },
+            ..
+        }
+        | Command::Generate {
+            command: GenerateCommand::Favorites { xmltv, .. },
+            ..
+        } => Some(xmltv),
+        _ => None,
+    }
+}
+
 async fn create_xmltv(
     path: PathBuf,
     mut games: Vec<Game>,
-    cdn: &Cdn,
-    quality: &Option<Quality>,
+    opts: &Opt,
     start_channel: u32,
+    pre_pad: Duration,
+    duration: Duration,
+    channel_icon: &Option<String>,
 ) -> Result<(), Error> {
+    let mut icon_cache = IconCache::new(opts).await;
+    let xmltv = build_xmltv(
+        &mut games,
+        opts,
+        start_channel,
+        pre_pad,
+        duration,
+        channel_icon,
+        &mut icon_cache,
+    )
+    .await?;
+
+    fs::write(&path, encode(xmltv, opts)).await?;
+
+    println!("Xmltv file saved to: {:?}", path);
+
+    Ok(())
+}
+
+/// Build the XMLTV guide, with a channel for each stream with a link numbered the same
+/// as in the playlist. Icons are always linked by their url, since --download-icons is
+/// only used when generate writes the guide.
+pub async fn xmltv(
+    games: &mut [Game],
+    opts: &Opt,
+    start_channel: u32,
+    pre_pad: Duration,
+    duration: Duration,
+    channel_icon: &Option<String>,
+) -> Result<String, Error> {
+    build_xmltv(
+        games,
+        opts,
+        start_channel,
+        pre_pad,
+        duration,
+        channel_icon,
+        &mut IconCache::links(),
+    )
+    .await
+}
+
+async fn build_xmltv(
+    games: &mut [Game],
+    opts: &Opt,
+    start_channel: u32,
+    pre_pad: Duration,
+    duration: Duration,
+    channel_icon: &Option<String>,
+    icon_cache: &mut IconCache,
+) -> Result<String, Error> {
     let mut xmltv = String::new();
     xmltv.push_str(&format!(
         "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
@@ -143,29 +903,31 @@ async fn create_xmltv(
         VERSION
     ));
 
-    let mut id: u32 = 0;
-    while id < 100 {
-        let record = format!(
-            "\n    <channel id=\"{}\">\
-             \n      <display-name>Lazyman {}</display-name>\
-             \n      <icon src=\"http://home.windstream.net/dgrodecki/images/nhl/nhl_logo2.jpg\"></icon>\
-             \n    </channel>",
-            start_channel + id,
-            id + 1
-        );
-        xmltv.push_str(&record);
-        id += 1;
-    }
+    let step = channel_step(opts);
+    let dialect = xmltv_options(opts)
+        .map(|xmltv| xmltv.xmltv_dialect.clone())
+        .unwrap_or(XmltvDialect::Default);
 
+    let mut programmes = String::new();
+    let mut channel_icons = vec![];
     let mut id: u32 = 0;
     for game in games.iter_mut() {
-        let icons = if let Some(game_cuts) = game.game_cuts().await {
+        let game_cuts = game.game_cuts().await;
+        let game_icon = channel_icon
+            .clone()
+            .or_else(|| game_cuts.as_ref().map(|cuts| cuts.cut_320_180.src.clone()))
+            .unwrap_or_else(|| String::from(NHL_LOGO));
+        let game_icon = icon_cache.get(&game_icon, game.client(), opts).await;
+
+        let icons = if let Some(game_cuts) = game_cuts {
             let cuts = vec![&game_cuts.cut_320_180, &game_cuts.cut_2048_1152];
             let mut icons = String::new();
             for cut in cuts {
                 let icon = format!(
                     "\n      <icon src=\"{}\" width=\"{}\" height=\"{}\"></icon>",
-                    cut.src, cut.width, cut.height,
+                    xml_escape(&icon_cache.get(&cut.src, game.client(), opts).await),
+                    cut.width,
+                    cut.height,
                 );
                 icons.push_str(&icon);
             }
@@ -174,56 +936,213 @@ async fn create_xmltv(
             String::from("\n      <icon src=\"\"></icon>")
         };
 
-        let description = game.description().await.unwrap_or_else(|| String::from(""));
+        let description = xmltv_description(game, opts).await;
+        let upcoming = game.status.state() == GameState::Preview;
 
-        for (_, stream) in game.streams.as_mut().unwrap().iter_mut() {
-            let link = if let Some(quality) = quality {
-                stream.quality_link(cdn, quality).await
-            } else {
-                stream.master_link(cdn).await
-            };
+        let streams = match game.streams.as_mut() {
+            Some(streams) => streams,
+            None => {
+                warn!("Skipping game {}, its streams failed to load", game.game_pk);
+                continue;
+            }
+        };
+        for (_, stream) in streams.iter_mut() {
+            let link = entry_link(stream, upcoming, opts).await;
 
             if link.is_ok() {
-                let start = Local::now();
-                let stop = Local::now();
+                let start = game.game_date - pre_pad;
+                let stop = start + duration;
                 let title = format!(
                     "{} {} {} @ {}",
-                    game.game_date
-                        .with_timezone(&Local)
-                        .time()
-                        .format("%-I:%M %p")
-                        .to_string(),
-                    stream.feed_type,
+                    format_time(game.game_date, opts.timezone, "%-I:%M %p"),
+                    stream.label(),
                     game.away_team.team_name,
                     game.home_team.team_name,
                 );
 
+                let mut extra = String::new();
+                if dialect != XmltvDialect::Default {
+                    extra.push_str("\n      <category lang=\"en\">Sports</category>");
+                }
+                // Plex skips recording programmes it thinks are reruns
+                if dialect == XmltvDialect::Plex {
+                    extra.push_str(&format!(
+                        "\n      <episode-num system=\"original-air-date\">{}</episode-num>\
+                         \n      <new />",
+                        format_time(game.game_date, opts.timezone, "%Y-%m-%d %H:%M:%S")
+                    ));
+                }
+
                 let record = format!(
-                    "\n    <programme channel=\"{}\" start=\"{}000000 {}\" stop=\"{}235959 {}\">\
+                    "\n    <programme channel=\"{}\" start=\"{}\" stop=\"{}\">\
                      \n      <title lang=\"en\">{}</title>\
                      \n      <desc lang=\"en\">{}</desc>\
-                     {}\
+                     {}{}\
                      \n    </programme>",
-                    start_channel + id,
-                    start.format("%Y%m%d"),
-                    start.format("%z"),
-                    stop.format("%Y%m%d"),
-                    stop.format("%z"),
-                    title,
-                    description,
+                    channel_number(start_channel, id, step)?,
+                    format_time(start, opts.timezone, "%Y%m%d%H%M%S %z"),
+                    format_time(stop, opts.timezone, "%Y%m%d%H%M%S %z"),
+                    xml_escape(&title),
+                    xml_escape(&description),
                     icons,
+                    extra,
                 );
-                xmltv.push_str(&record);
+                programmes.push_str(&record);
+                channel_icons.push(game_icon.clone());
                 id += 1;
             }
         }
     }
 
+    // Only create as many channels as there are programmes
+    for (channel, icon) in (0..id).zip(channel_icons) {
+        let record = format!(
+            "\n    <channel id=\"{}\">\
+             \n      <display-name>Lazyman {}</display-name>\
+             \n      <icon src=\"{}\"></icon>\
+             \n    </channel>",
+            channel_number(start_channel, channel, step)?,
+            channel + 1,
+            xml_escape(&icon)
+        );
+        xmltv.push_str(&record);
+    }
+
+    xmltv.push_str(&programmes);
     xmltv.push_str("\n  </tv>");
 
-    fs::write(&path, xmltv).await?;
+    Ok(xmltv)
+}
 
-    println!("Xmltv file saved to: {:?}", path);
+/// Description of the programme, the game's preview followed by its venue and national
+/// broadcasts, leaving out any the stats api doesn't have. Games in progress also get the
+/// period and clock, unless --no-scores is passed.
+async fn xmltv_description(game: &mut Game, opts: &Opt) -> String {
+    let mut lines = vec![];
+    if let Some(progress) = &game.status.progress {
+        if game.status.state() == GameState::Live && !opts.no_scores {
+            lines.push(format!("In progress: {}", progress));
+        }
+    }
+    if let Some(description) = game.description().await {
+        if !description.is_empty() {
+            lines.push(description);
+        }
+    }
+    if let Some(venue) = &game.venue {
+        lines.push(format!("Venue: {}", venue));
+    }
+    if !game.national_broadcasts.is_empty() {
+        lines.push(format!(
+            "National broadcast: {}",
+            game.national_broadcasts.join(", ")
+        ));
+    }
 
-    Ok(())
+    lines.join("\n")
+}
+
+/// Icons downloaded for --download-icons, so each is only fetched once even when
+/// it's shared by many games
+struct IconCache {
+    dir: Option<PathBuf>,
+    paths: HashMap<String, String>,
+}
+
+impl IconCache {
+    async fn new(opts: &Opt) -> Self {
+        let download_icons = xmltv_options(opts).and_then(|xmltv| xmltv.download_icons.as_ref());
+        let dir = match (&opts.command, download_icons) {
+            (Command::Generate { options, .. }, Some(dir)) if !options.dry_run => {
+                let out_dir = options
+                    .out_dir
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("."));
+                icon_dir(&out_dir.join(dir)).await
+            }
+            _ => None,
+        };
+
+        IconCache {
+            dir,
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Keep every icon as its url, without downloading any
+    fn links() -> Self {
+        IconCache {
+            dir: None,
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Local path of the icon, downloading it if it isn't saved yet. Falls back to the
+    /// url if icons aren't being downloaded or the download fails.
+    async fn get(&mut self, url: &str, client: &stream::Client, opts: &Opt) -> String {
+        let dir = match &self.dir {
+            Some(dir) if !url.is_empty() => dir,
+            _ => return url.to_string(),
+        };
+
+        if let Some(path) = self.paths.get(url) {
+            return path.clone();
+        }
+
+        let path = dir.join(icon_file_name(url));
+        let saved = fs::metadata(&path).await.is_ok() || {
+            match stream::download(client, url, opts.retries).await {
+                Ok(bytes) => fs::write(&path, bytes).await.is_ok(),
+                Err(e) => {
+                    warn!("Failed to download icon {}: {}", url, e);
+                    false
+                }
+            }
+        };
+
+        let local = if saved {
+            path.to_string_lossy().to_string()
+        } else {
+            url.to_string()
+        };
+        self.paths.insert(url.to_string(), local.clone());
+        local
+    }
+}
+
+/// Create the icon directory, returning its absolute path so the guide works from
+/// anywhere
+async fn icon_dir(dir: &Path) -> Option<PathBuf> {
+    let result = async {
+        fs::create_dir_all(dir).await?;
+        fs::canonicalize(dir).await
+    };
+
+    match result.await {
+        Ok(dir) => Some(dir.into()),
+        Err(e) => {
+            warn!("Failed to create icon directory {:?}: {}", dir, e);
+            None
+        }
+    }
+}
+
+/// File name for the icon, unique to its url but keeping the extension. The name is a
+/// hash of the url that's the same across runs and builds, so already downloaded icons
+/// are found again.
+fn icon_file_name(url: &str) -> String {
+    let hash = fnv1a(url.as_bytes());
+
+    let file = url.split('?').next().unwrap_or_default();
+    match Path::new(file).extension() {
+        Some(extension) => format!("{:016x}.{}", hash, extension.to_string_lossy()),
+        None => format!("{:016x}", hash),
+    }
+}
+
+/// 64 bit FNV-1a hash, which unlike the standard library's hasher is stable
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
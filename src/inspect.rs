@@ -0,0 +1,80 @@
+use crate::{
+    log_error,
+    opt::{Command, Opt},
+    stream,
+};
+use async_std::{process, task};
+use failure::Error;
+
+pub fn run(opts: Opt) {
+    task::block_on(async {
+        if let Err(e) = process(opts).await {
+            log_error(&e);
+            process::exit(1);
+        };
+    });
+}
+
+async fn process(opts: Opt) -> Result<(), Error> {
+    let url = match &opts.command {
+        Command::Inspect { url } => url.clone(),
+        _ => None,
+    };
+
+    let (client, link) = match url {
+        Some(url) => (stream::Client::new(&opts), url),
+        None => {
+            let (game, mut stream) = crate::select::process(&opts, true).await?;
+            println!(
+                "Inspecting {} @ {} {}...\n",
+                game.away_team.name, game.home_team.name, stream.feed_type
+            );
+
+            let link = match &opts.quality {
+                Some(quality) => stream.quality_link(&opts.cdn, quality).await?,
+                None => stream.master_link(&opts.cdn).await?,
+            };
+            (game.client().clone(), link)
+        }
+    };
+
+    let playlist = stream::inspect(&client, &link, opts.retries).await?;
+
+    let kind = match (
+        playlist.live,
+        playlist.playlist_type.as_ref().map(String::as_str),
+    ) {
+        (false, _) => "VOD, the stream has ended",
+        (true, Some("EVENT")) => "Live, full DVR from the start",
+        (true, _) => "Live, only the most recent segments",
+    };
+
+    let target_duration = playlist
+        .target_duration
+        .map(|target| format!("{}s", target))
+        .unwrap_or_else(|| String::from("-"));
+
+    println!("{:<18}{}", "Media playlist:", playlist.link);
+    println!("{:<18}{}", "Segments:", playlist.segments);
+    println!("{:<18}{}", "Target duration:", target_duration);
+    println!(
+        "{:<18}{}",
+        "Total duration:",
+        format_duration(playlist.duration)
+    );
+    println!("{:<18}{}", "Type:", kind);
+
+    Ok(())
+}
+
+/// Seconds as hours, minutes and seconds, ie. '2h 31m 12s'
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {}s", minutes, seconds)
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    log_error,
+    opt::Opt,
+    stream::{LazyStream, Stream},
+};
+use async_std::{process, task};
+use failure::Error;
+use serde::Serialize;
+
+pub fn run(opts: Opt) {
+    task::block_on(async {
+        if let Err(e) = process(opts).await {
+            log_error(&e);
+            process::exit(1);
+        };
+    });
+}
+
+async fn process(opts: Opt) -> Result<(), Error> {
+    let mut lazy_stream = LazyStream::new(&opts).await?;
+
+    if let Some(quality) = &opts.quality {
+        lazy_stream
+            .resolve_with_quality_link(&opts.cdn, quality, opts.concurrency)
+            .await;
+    } else {
+        lazy_stream
+            .resolve_with_master_link(&opts.cdn, opts.concurrency)
+            .await;
+    }
+
+    let mut games = vec![];
+    for mut game in lazy_stream.games() {
+        let mut feeds = vec![];
+
+        if let Some(streams) = game.streams.as_mut() {
+            let mut streams: Vec<&mut Stream> = streams.values_mut().collect();
+            streams.sort_by_key(|stream| stream.feed_type.clone());
+
+            for stream in streams {
+                let master_link = stream.master_link(&opts.cdn).await.ok();
+                let quality_link = if let Some(quality) = &opts.quality {
+                    stream.quality_link(&opts.cdn, quality).await.ok()
+                } else {
+                    None
+                };
+
+                feeds.push(JsonFeed {
+                    feed_type: stream.feed_type.to_string(),
+                    host_link: stream.host_link(&opts.cdn),
+                    master_link,
+                    quality_link,
+                });
+            }
+        }
+
+        games.push(JsonGame {
+            game_pk: game.game_pk,
+            game_date: game.game_date.to_rfc3339(),
+            away_team: JsonTeam {
+                name: game.away_team.name.clone(),
+                team_name: game.away_team.team_name.clone(),
+                abbreviation: game.away_team.abbreviation.clone(),
+            },
+            home_team: JsonTeam {
+                name: game.home_team.name.clone(),
+                team_name: game.home_team.team_name.clone(),
+                abbreviation: game.home_team.abbreviation.clone(),
+            },
+            feeds,
+        });
+    }
+
+    let output = JsonOutput {
+        date: lazy_stream.date().format("%Y-%m-%d").to_string(),
+        games,
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonOutput {
+    date: String,
+    games: Vec<JsonGame>,
+}
+
+#[derive(Serialize)]
+struct JsonGame {
+    game_pk: u64,
+    game_date: String,
+    away_team: JsonTeam,
+    home_team: JsonTeam,
+    feeds: Vec<JsonFeed>,
+}
+
+#[derive(Serialize)]
+struct JsonTeam {
+    name: String,
+    team_name: String,
+    abbreviation: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeed {
+    feed_type: String,
+    host_link: String,
+    master_link: Option<String>,
+    quality_link: Option<String>,
+}
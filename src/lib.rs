@@ -0,0 +1,109 @@
+//! Get LazyMan stream links for NHL games. Games and their streams are fetched with
+//! `stream::LazyStream`, or all at once for generating output with `generate::games`.
+//! `generate::playlist` and `generate::xmltv` build output from them without writing
+//! any files.
+
+use crate::opt::OutputType;
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use colored::Colorize;
+use failure::Error;
+
+mod doctor;
+pub mod generate;
+mod inspect;
+mod json;
+mod logger;
+pub mod opt;
+mod qualities;
+mod select;
+pub mod stream;
+mod streamlink;
+mod update;
+mod watch;
+
+const VERSION: &str = "1.7.1";
+const HOST: &str = "http://nhl.freegamez.ga";
+const BANNER: &str = r#"
+ |        \   __  /\ \   / ___|__ __|  _ \  ____|    \     \  | 
+ |       _ \     /  \   /\___ \   |   |   | __|     _ \   |\/ | 
+ |      ___ \   /      |       |  |   __ <  |      ___ \  |   | 
+_____|_/    _\____|   _| _____/  _|  _| \_\_____|_/    _\_|  _| 
+"#;
+
+/// Parse the command line and run its command, after checking for a newer version
+pub fn run_cli() {
+    let output_type = opt::parse_opts();
+    check_for_update(&output_type);
+
+    run(output_type);
+}
+
+/// Run the command parsed from the command line
+fn run(output_type: OutputType) {
+    match output_type {
+        OutputType::Select(opts) => crate::select::run(opts),
+        OutputType::Generate(opts) => crate::generate::run(opts),
+        OutputType::Watch(opts) => crate::watch::run(opts),
+        OutputType::Record(opts) => crate::streamlink::run(opts),
+        OutputType::Cast(opts) => crate::streamlink::run(opts),
+        OutputType::Json(opts) => crate::json::run(opts),
+        OutputType::Qualities(opts) => crate::qualities::run(opts),
+        OutputType::Doctor(opts) => crate::doctor::run(opts),
+        OutputType::Inspect(opts) => crate::inspect::run(opts),
+    }
+}
+
+/// Warn if a newer version of lazystream is available. Skipped with --no-update-check,
+/// for json output and when stdout isn't a terminal, ie. when run from cron
+fn check_for_update(output_type: &OutputType) {
+    if let OutputType::Json(_) = output_type {
+        return;
+    }
+    let opts = output_type.opts();
+    if opts.no_update_check || !atty::is(atty::Stream::Stdout) {
+        return;
+    }
+
+    crate::update::check(&crate::stream::Client::new(opts));
+}
+
+/// Log any errors and causes
+pub(crate) fn log_error(e: &Error) {
+    let error_colored = "ERROR".red();
+    eprintln!("\n{}: {}", error_colored, e);
+    for cause in e.iter_causes() {
+        let caused_colored = "Caused by:".yellow();
+        eprintln!("\n{} {}", caused_colored, cause);
+    }
+}
+
+/// Print status messages, to stderr when stdout is reserved for the output so they
+/// don't end up in it
+pub(crate) fn status(to_stderr: bool, msg: &str) {
+    if to_stderr {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
+
+/// Format time in the timezone if specified, otherwise in local time
+pub(crate) fn format_time(time: DateTime<Utc>, timezone: Option<Tz>, fmt: &str) -> String {
+    match timezone {
+        Some(timezone) => time.with_timezone(&timezone).format(fmt).to_string(),
+        None => time.with_timezone(&Local).format(fmt).to_string(),
+    }
+}
+
+/// Replace characters that aren't allowed in file names on Windows, so generated
+/// names work everywhere
+pub(crate) fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect()
+}
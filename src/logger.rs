@@ -0,0 +1,80 @@
+use chrono::Local;
+use failure::{format_err, Error, ResultExt};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// Logs from lazystream only, to stderr or a file
+struct Logger {
+    level: Level,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with("lazystream")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.args()
+        );
+
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write_all(line.as_bytes());
+            }
+        } else {
+            eprint!("{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Set up logging for the verbosity, nothing is logged without one unless a log
+/// file is used
+pub fn init(verbose: u8, log_file: Option<&PathBuf>) -> Result<(), Error> {
+    let level = match verbose {
+        0 if log_file.is_some() => Level::Debug,
+        0 => return Ok(()),
+        1 => Level::Info,
+        2 => Level::Debug,
+        _ => Level::Trace,
+    };
+
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .context(format_err!("Failed to open log file {:?}", path))?;
+            Some(Mutex::new(file))
+        }
+        None => None,
+    };
+
+    log::set_boxed_logger(Box::new(Logger { level, file }))
+        .map_err(|_| format_err!("Logger already initialized"))?;
+    log::set_max_level(LevelFilter::Trace);
+
+    Ok(())
+}
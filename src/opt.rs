@@ -1,18 +1,73 @@
-use crate::VERSION;
-use chrono::{format::ParseError, NaiveDate};
-use failure::{bail, Error};
+use crate::{log_error, HOST, VERSION};
+use chrono::{Duration, Local, NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use colored::Colorize;
+use failure::{bail, format_err, Error, ResultExt};
 use http::Uri;
-use std::{net::Ipv4Addr, path::PathBuf, str::FromStr};
-use structopt::{clap::AppSettings::DeriveDisplayOrder, StructOpt};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, env, fs, net::Ipv4Addr, path::PathBuf, str::FromStr};
+use structopt::{
+    clap::{AppSettings::DeriveDisplayOrder, ArgMatches},
+    StructOpt,
+};
+
+const CONFIG_FILE: &str = "lazystream.toml";
+const STATE_FILE: &str = "state.toml";
+/// Longest duration accepted for options like --pre-pad, a year
+const MAX_DURATION_SECS: i64 = 366 * 24 * 60 * 60;
 
 pub fn parse_opts() -> OutputType {
-    let opts = Opt::from_args();
+    let matches = Opt::clap().get_matches();
+    let mut opts = Opt::from_clap(&matches);
+
+    if opts.forget {
+        if let Err(e) = State::clear() {
+            log_error(&e);
+            std::process::exit(1);
+        }
+    }
+
+    let config = Config::load().map(|config| {
+        if opts.uses_state() {
+            config.with_state(State::load(), &opts)
+        } else {
+            config
+        }
+    });
+    if let Err(e) = config.and_then(|config| opts.apply_config(config, &matches)) {
+        log_error(&e);
+        std::process::exit(1);
+    }
+
+    // Stream links are resolved to a quality link, picked from those under the cap
+    if opts.max_quality.is_some() {
+        opts.quality = Some(Quality::Best);
+    }
+
+    if let Err(e) = crate::logger::init(opts.verbose, opts.log_file.as_ref()) {
+        log_error(&e);
+        std::process::exit(1);
+    }
+
+    match opts.color {
+        ColorChoice::Always => colored::control::set_override(true),
+        ColorChoice::Never => colored::control::set_override(false),
+        ColorChoice::Auto if env::var_os("NO_COLOR").is_some() => {
+            colored::control::set_override(false)
+        }
+        ColorChoice::Auto => {}
+    }
 
     match opts.command {
         Command::Select { .. } => OutputType::Select(opts),
+        Command::Generate { ref options, .. } if options.watch => OutputType::Watch(opts),
         Command::Generate { .. } => OutputType::Generate(opts),
         Command::Record { .. } => OutputType::Record(opts),
         Command::Cast { .. } => OutputType::Cast(opts),
+        Command::Json => OutputType::Json(opts),
+        Command::Qualities => OutputType::Qualities(opts),
+        Command::Doctor => OutputType::Doctor(opts),
+        Command::Inspect { .. } => OutputType::Inspect(opts),
     }
 }
 
@@ -27,31 +82,473 @@ pub fn parse_opts() -> OutputType {
 pub struct Opt {
     #[structopt(subcommand)]
     pub command: Command,
-    #[structopt(long, parse(try_from_str = parse_date), value_name = "YYYYMMDD", global = true)]
+    #[structopt(
+        long,
+        parse(try_from_str = parse_date),
+        value_name = "YYYYMMDD",
+        global = true,
+        allow_hyphen_values = true
+    )]
     /// Specify what date to use for games, defaults to today
+    ///
+    /// Also accepts 'today', 'yesterday', 'tomorrow' or a day offset from today, ie. '-2' or '+1'
     pub date: Option<NaiveDate>,
     #[structopt(long, parse(try_from_str), default_value = Cdn::Akc.into(), global = true)]
     /// Specify which CDN to use: 'akc' or 'l3c'
     pub cdn: Cdn,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_host),
+        value_name = "URL",
+        number_of_values = 1,
+        global = true
+    )]
+    /// Specify the host to get stream links from, ie. 'http://example.com'. Defaults to
+    /// http://nhl.freegamez.ga
+    ///
+    /// Can be passed more than once, ie. '--host http://a.com --host http://b.com', to try
+    /// each host in order until one has the stream
+    pub host: Vec<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_header),
+        value_name = "NAME: VALUE",
+        number_of_values = 1,
+        global = true
+    )]
+    /// Extra header to send with each request to the host, ie. 'Authorization: Bearer
+    /// TOKEN', for hosts that need a login. Can be passed more than once. Headers aren't
+    /// sent to the CDN or the NHL stats api
+    pub header: Vec<(String, String)>,
+    #[structopt(long, value_name = "COOKIE", global = true)]
+    /// Cookie to send with each request to the host, ie. 'session=abc123'. Same as
+    /// passing '--header "Cookie: session=abc123"'
+    pub cookie: Option<String>,
+    #[structopt(long, global = true)]
+    /// Don't fall back to the other CDN when a stream can't be resolved on the one specified
+    pub no_fallback: bool,
     #[structopt(long, parse(try_from_str), global = true)]
     /// Specify a quality to use, otherwise stream will be adaptive. Doesn't currently affect recording.
     ///
-    /// Must be one of: '720p60', '720p', '540p', '504p', '360p', '288p', '224p', '216p'
+    /// Must be one of: 'best', 'worst', '720p60', '720p', '540p', '504p', '360p', '288p', '224p',
+    /// '216p'. 'best' and 'worst' pick the highest / lowest bandwidth stream available.
+    /// Resolutions can also be passed without the 'p', ie. '720'.
     pub quality: Option<Quality>,
+    #[structopt(long, global = true)]
+    /// If the quality specified isn't offered for a stream, use the nearest one available
+    /// instead of failing
+    pub quality_fallback: bool,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_max_quality),
+        value_name = "RESOLUTION",
+        conflicts_with = "quality",
+        global = true
+    )]
+    /// Use the highest quality at or below this resolution, ie. '720p', instead of an exact
+    /// quality. With --quality-fallback, the lowest quality is used if none are at or below
+    pub max_quality: Option<u32>,
+    #[structopt(long, parse(try_from_str), use_delimiter = true, global = true)]
+    /// Only include these feed types when generating output, ie. 'home,away'. Includes all
+    /// feeds if not specified
+    ///
+    /// Must be one of: 'home', 'away', 'national', 'french', 'composite'
+    pub feeds: Vec<FeedType>,
+    #[structopt(long, global = true)]
+    /// Use the audio only radio feeds instead of the video feeds
+    pub audio: bool,
+    #[structopt(long, value_name = "NAME", global = true)]
+    /// Play this alternate audio track of the stream, ie. 'home' or 'away' commentary,
+    /// when using the 'streamlink' or 'mpv' player. Tracks can be listed with the
+    /// qualities subcommand
+    pub audio_track: Option<String>,
+    #[structopt(long, default_value = "6", global = true)]
+    /// How many games to resolve stream links for at once when getting links for all games
+    pub concurrency: usize,
+    #[structopt(long, global = true)]
+    /// Only show games for teams with a name or abbreviation matching TEAM, ie. 'Bruins' or
+    /// 'BOS'. If a single game matches, it will be selected automatically
+    pub team: Option<String>,
+    #[structopt(long, parse(try_from_str), global = true)]
+    /// Specify the timezone game times are shown in, ie. 'America/New_York'. Defaults to the
+    /// system's local timezone
+    pub timezone: Option<Tz>,
+    #[structopt(long, global = true)]
+    /// Don't show scores, or the period and clock of games in progress in the xmltv guide,
+    /// to avoid spoilers
+    pub no_scores: bool,
+    #[structopt(long, global = true)]
+    /// Only include games that are in progress or haven't started yet, hiding finished games
+    pub live_only: bool,
+    #[structopt(long, global = true)]
+    /// Fetch the schedule from the stats api, instead of using the copy cached within the
+    /// last 10 minutes
+    pub refresh: bool,
+    #[structopt(long, global = true)]
+    /// If streams aren't available yet for the selected game, keep checking until they are.
+    /// Also keeps checking when the feed is listed but its link isn't being served yet
+    pub wait: bool,
+    #[structopt(long, default_value = "30", value_name = "SECS", global = true)]
+    /// How often to check for streams when using --wait
+    pub wait_interval: u64,
+    #[structopt(long, default_value = "60", value_name = "MINS", global = true)]
+    /// How long to keep checking for streams when using --wait before giving up
+    pub wait_timeout: u64,
+    #[structopt(long, parse(try_from_str), default_value = ColorChoice::Auto.into(), global = true)]
+    /// When to use colored output: 'auto', 'always' or 'never'. 'auto' only colors output
+    /// to a terminal, and respects the NO_COLOR environment variable
+    pub color: ColorChoice,
+    #[structopt(short, long, parse(from_occurrences), global = true)]
+    /// Log what requests are made and which CDN / quality is used to stderr. Pass multiple
+    /// times for more detail, ie. '-vv'
+    pub verbose: u8,
+    #[structopt(long, parse(from_os_str), value_name = "PATH", global = true)]
+    /// Write logs to PATH instead of stderr. Logs in detail unless a verbosity is set
+    pub log_file: Option<PathBuf>,
+    #[structopt(long, parse(try_from_str), value_name = "URL", global = true)]
+    /// Proxy server to use for all requests and Streamlink, ie. 'http://host:port' or
+    /// 'socks5://host:port'. Can also be passed after the record and cast subcommands
+    ///
+    /// Without it, requests use the proxy from the 'http_proxy', 'HTTPS_PROXY' or
+    /// 'ALL_PROXY' environment variables. Only the lowercase 'http_proxy' is read.
+    pub proxy: Option<Uri>,
+    #[structopt(long, default_value = "3", value_name = "ATTEMPTS", global = true)]
+    /// How many times to attempt each request before giving up, retrying on connection
+    /// errors and server errors
+    pub retries: u32,
+    #[structopt(long, default_value = "15", value_name = "SECS", global = true)]
+    /// How long to wait for each request before giving up on it. Timed out requests are
+    /// retried, and fall back to the other CDN once retries run out
+    pub timeout: u64,
+    #[structopt(long, global = true)]
+    /// Don't warn when the selected game hasn't started yet or finished a while ago
+    pub force: bool,
+    #[structopt(long, global = true)]
+    /// Don't use or remember the CDN and quality of the last stream selected, which are
+    /// otherwise the defaults for the next select
+    pub no_remember: bool,
+    #[structopt(long, global = true)]
+    /// Forget the remembered CDN and quality, going back to the defaults
+    pub forget: bool,
+    #[structopt(long, global = true)]
+    /// Don't check GitHub for a newer version of lazystream at startup. Never checked for
+    /// json output or when stdout isn't a terminal
+    pub no_update_check: bool,
+    #[structopt(long, global = true)]
+    /// Don't print the banner, which otherwise goes to stderr when selecting a stream
+    pub no_banner: bool,
+}
+
+impl Opt {
+    /// Hosts to get stream links from, in the order to try them
+    pub fn hosts(&self) -> Vec<String> {
+        if self.host.is_empty() {
+            vec![HOST.to_string()]
+        } else {
+            self.host.clone()
+        }
+    }
+
+    /// Extra headers to send to the host, including the cookie
+    pub fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = self.header.clone();
+        if let Some(cookie) = &self.cookie {
+            headers.push((String::from("Cookie"), cookie.clone()));
+        }
+        headers
+    }
+
+    /// Use config file values for any options not passed on the command line
+    fn apply_config(&mut self, config: Config, matches: &ArgMatches) -> Result<(), Error> {
+        if let Some(cdn) = config.cdn {
+            // Cdn has a default value, so check if it was actually passed
+            if !arg_passed(matches, "cdn") {
+                self.cdn = cdn.parse::<Cdn>().context("Invalid cdn in config file")?;
+            }
+        }
+
+        if self.host.is_empty() {
+            let hosts = match config.host {
+                Some(Hosts::One(host)) => vec![host],
+                Some(Hosts::Many(hosts)) => hosts,
+                None => vec![],
+            };
+            for host in hosts {
+                let host = parse_host(&host).context("Invalid host in config file")?;
+                self.host.push(host);
+            }
+        }
+
+        if self.quality.is_none() {
+            if let Some(quality) = config.quality {
+                let quality = quality
+                    .parse::<Quality>()
+                    .context("Invalid quality in config file")?;
+                self.quality = Some(quality);
+            }
+        }
+
+        if self.team.is_none() {
+            self.team = config.team;
+        }
+
+        // The configured player isn't used when only the link is wanted, including when
+        // stdout is captured, ie. 'vlc $(lazystream select ...)'
+        if let Command::Select {
+            player,
+            record,
+            raw,
+            all_feeds,
+            list,
+            ..
+        } = &mut self.command
+        {
+            let link_only = *raw || *all_feeds || *list || !atty::is(atty::Stream::Stdout);
+            if player.is_none() && record.is_none() && !link_only {
+                *player = config.player;
+            }
+        }
+
+        if let Command::Generate {
+            command: GenerateCommand::Favorites { teams, .. },
+            ..
+        } = &mut self.command
+        {
+            if teams.is_empty() {
+                *teams = config.favorites.unwrap_or_default();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remembered options are only used, and saved, when picking a stream interactively
+    /// with select, so they're read and written by the same runs
+    fn uses_state(&self) -> bool {
+        match self.command {
+            Command::Select { raw, .. } => {
+                !raw && atty::is(atty::Stream::Stdin) && !self.no_remember && !self.forget
+            }
+            _ => false,
+        }
+    }
+
+    /// Remember the CDN and quality a link was resolved with, to default to them next select
+    pub fn remember(&self, cdn: &Cdn) {
+        if !self.uses_state() {
+            return;
+        }
+
+        let state = State {
+            cdn: Some(cdn.to_string()),
+            // A --max-quality cap isn't a quality to default to next run
+            quality: self
+                .quality
+                .as_ref()
+                .filter(|_| self.max_quality.is_none())
+                .map(|quality| quality.arg().to_string()),
+        };
+        if let Err(e) = state.save() {
+            eprintln!(
+                "\n{}: Failed to remember options, {}",
+                "WARNING".yellow(),
+                e
+            );
+        }
+    }
+}
+
+/// Default options loaded from a lazystream.toml file. Options passed on the
+/// command line always take precedence.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    host: Option<Hosts>,
+    cdn: Option<String>,
+    quality: Option<String>,
+    team: Option<String>,
+    player: Option<String>,
+    favorites: Option<Vec<String>>,
+}
+
+/// A single host, or a list of hosts to try in order
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Hosts {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Config {
+    /// Load from the current directory, otherwise from the user's config directory
+    fn load() -> Result<Config, Error> {
+        let path = match config_paths().into_iter().find(|path| path.is_file()) {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        let contents = fs::read_to_string(&path)
+            .context(format_err!("Failed to read config file {:?}", path))?;
+        let config = toml::from_str::<Config>(&contents)
+            .context(format_err!("Failed to parse config file {:?}", path))?;
+
+        Ok(config)
+    }
+
+    /// Fall back to the last used options for any not set in the config file
+    fn with_state(mut self, state: State, opts: &Opt) -> Config {
+        self.cdn = self.cdn.or(state.cdn);
+        // Only the adaptive stream has audio tracks, so --audio-track wins over a
+        // remembered quality
+        if opts.audio_track.is_none() {
+            self.quality = self.quality.or(state.quality);
+        }
+        self
+    }
+}
+
+/// Options last used successfully, saved automatically after a stream resolves
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct State {
+    cdn: Option<String>,
+    quality: Option<String>,
+}
+
+impl State {
+    /// Load the saved state, ignoring it if missing or unreadable
+    fn load() -> State {
+        state_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| toml::from_str::<State>(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = state_path().ok_or_else(|| format_err!("no config directory found"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    fn clear() -> Result<(), Error> {
+        match state_path() {
+            Some(path) if path.is_file() => fs::remove_file(&path)
+                .context(format_err!("Failed to forget options in {:?}", path))
+                .map_err(Error::from),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Config file locations, in order of priority
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILE)];
+
+    if let Some(config_dir) = config_dir() {
+        paths.push(config_dir.join(CONFIG_FILE));
+    }
+
+    paths
+}
+
+fn state_path() -> Option<PathBuf> {
+    config_dir().map(|config_dir| config_dir.join(STATE_FILE))
+}
+
+/// The user's lazystream config directory
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config_dir| config_dir.join("lazystream"))
+}
+
+/// Check if an argument was passed, rather than set from its default value. Global
+/// arguments can be passed after any of the subcommands, so they're all checked.
+fn arg_passed(matches: &ArgMatches, name: &str) -> bool {
+    matches.occurrences_of(name) > 0
+        || matches
+            .subcommand()
+            .1
+            .map_or(false, |matches| arg_passed(matches, name))
 }
 
 #[derive(StructOpt, Debug, PartialEq, Clone)]
 pub enum Command {
-    #[structopt(usage = "lazystream select [--resolve] [OPTIONS]")]
+    #[structopt(
+        usage = "lazystream select [--resolve --player <COMMAND> --record <PATH> --list --feed <FEED> --from-start --game-pk <ID>] [OPTIONS]"
+    )]
     /// Select stream link via command line
     Select {
         #[structopt(long)]
         /// Resolve url to the actual hls link, if it's available
         resolve: bool,
+        #[structopt(long, value_name = "COMMAND")]
+        /// Open the stream with a player instead of printing the link, ie. 'mpv', 'vlc' or 'streamlink'
+        ///
+        /// The link is always resolved to the actual hls link before being passed to the player.
+        player: Option<String>,
+        #[structopt(
+            long,
+            value_name = "PATH",
+            parse(from_os_str),
+            conflicts_with = "player"
+        )]
+        /// Record the stream to PATH with ffmpeg instead of printing the link, requires ffmpeg
+        ///
+        /// If PATH is a directory, the recording is saved to it as '{away} @ {home} {date}.mp4'.
+        /// Recording continues until the stream ends or Ctrl-C is pressed.
+        record: Option<PathBuf>,
+        #[structopt(long)]
+        /// Only list the games for the day and exit, without picking a stream
+        list: bool,
+        #[structopt(long, parse(try_from_str))]
+        /// Use this feed type instead of picking a stream, ie. 'home'. Combined with --team,
+        /// no prompts are needed
+        ///
+        /// Must be one of: 'home', 'away', 'national', 'french', 'composite'
+        feed: Option<FeedType>,
+        #[structopt(long, conflicts_with_all = &["feed", "player", "record"])]
+        /// Print the link of every feed of the picked game, labeled with its feed type,
+        /// instead of picking a stream
+        all_feeds: bool,
+        #[structopt(long)]
+        /// Start from the beginning of a live stream instead of the live edge, when using
+        /// --record or the 'streamlink' or 'mpv' player
+        from_start: bool,
+        #[structopt(long, value_name = "ID")]
+        /// Use the game with this id instead of picking one. It's looked up directly, so it
+        /// can be played on any day and --date isn't needed
+        game_pk: Option<u64>,
+        #[structopt(
+            long,
+            parse(try_from_str),
+            value_name = "AWAY@HOME",
+            conflicts_with = "game-pk"
+        )]
+        /// Use the game between these teams instead of picking one, ie. 'NYR@BOS'. More
+        /// precise than --team, and combined with --feed no prompts are needed
+        matchup: Option<Matchup>,
+        #[structopt(long)]
+        /// Don't print which game, feed, CDN and quality were used before the link
+        quiet: bool,
+        #[structopt(long)]
+        /// Only print the link to stdout, for scripts like 'vlc $(lazystream select ...)'.
+        /// The banner is left out and everything else goes to stderr. Any prompts needed
+        /// are an error instead, so use --team or --game-pk and --feed to pick the stream.
+        ///
+        /// Used automatically when stdin isn't a terminal.
+        raw: bool,
     },
     #[structopt(usage = "lazystream generate <SUBCOMMAND> [OPTIONS]", setting = DeriveDisplayOrder)]
     /// Generate an xmltv and/or playlist formatted output for all games
     Generate {
+        #[structopt(flatten)]
+        options: GenerateOptions,
         #[structopt(subcommand)]
         command: GenerateCommand,
     },
@@ -73,12 +570,31 @@ pub enum Command {
         #[structopt(subcommand)]
         command: CastCommand,
     },
+    #[structopt(usage = "lazystream json [OPTIONS]")]
+    /// Output all games and their stream links as JSON, without any prompts
+    Json,
+    #[structopt(usage = "lazystream qualities [OPTIONS]")]
+    /// Select a stream via command line and list all of its available qualities
+    Qualities,
+    #[structopt(usage = "lazystream doctor [OPTIONS]")]
+    /// Check which CDNs and qualities work, using the first game with streams available
+    Doctor,
+    #[structopt(usage = "lazystream inspect [URL] [OPTIONS]")]
+    /// Show the segment count, duration and type of a stream's media playlist, to see if
+    /// it's a full DVR or only the live edge. Select a stream via command line, or pass a
+    /// link to it. For master links, the highest bandwidth variant is used unless
+    /// --quality is set
+    Inspect {
+        #[structopt(name = "URL")]
+        /// Link to a master or media playlist, instead of selecting a stream
+        url: Option<String>,
+    },
 }
 
 #[derive(StructOpt, Debug, PartialEq, Clone)]
 pub enum RecordCommand {
     #[structopt(
-        usage = "lazystream record select <OUTPUT DIR> [--restart --proxy <PROXY>] [OPTIONS]"
+        usage = "lazystream record select <OUTPUT DIR> [--restart --proxy <URL>] [OPTIONS]"
     )]
     /// Select a game from the command line to record to OUTPUT DIR
     Select {
@@ -88,12 +604,9 @@ pub enum RecordCommand {
         #[structopt(long)]
         /// If live, restart the stream from the beginning and record the entire thing
         restart: bool,
-        #[structopt(long, parse(try_from_str))]
-        /// Proxy server address to be passed to Streamlink
-        proxy: Option<Uri>,
     },
     #[structopt(
-        usage = "lazystream record team <TEAM> <OUTPUT DIR> [--restart --feed-type <feed-type> --proxy <PROXY>] [OPTIONS]"
+        usage = "lazystream record team <TEAM> <OUTPUT DIR> [--restart --feed-type <feed-type> --proxy <URL>] [OPTIONS]"
     )]
     /// Specify team abbreviation. If / when stream is available, will record to OUTPUT DIR.
     ///
@@ -117,16 +630,13 @@ pub enum RecordCommand {
         /// Specify the feed type to download. Will default to supplied
         /// team's applicable Home / Away feed
         feed_type: Option<FeedType>,
-        #[structopt(long, parse(try_from_str))]
-        /// Proxy server address to be passed to Streamlink
-        proxy: Option<Uri>,
     },
 }
 
 #[derive(StructOpt, Debug, PartialEq, Clone)]
 pub enum CastCommand {
     #[structopt(
-        usage = "lazystream cast select <CHROMECAST IP> [--restart --proxy <PROXY>] [OPTIONS]"
+        usage = "lazystream cast select <CHROMECAST IP> [--restart --proxy <URL>] [OPTIONS]"
     )]
     /// Select a game from the command line to cast to CHROMECAST IP
     Select {
@@ -136,12 +646,9 @@ pub enum CastCommand {
         #[structopt(long)]
         /// If live, restart the stream from the beginning and cast the entire thing
         restart: bool,
-        #[structopt(long, parse(try_from_str))]
-        /// Proxy server address to be passed to Streamlink
-        proxy: Option<Uri>,
     },
     #[structopt(
-        usage = "lazystream cast team <TEAM> <CHROMECAST IP> [--restart --feed-type <feed-type> --proxy <PROXY>] [OPTIONS]"
+        usage = "lazystream cast team <TEAM> <CHROMECAST IP> [--restart --feed-type <feed-type> --proxy <URL>] [OPTIONS]"
     )]
     /// Specify team abbreviation. If / when stream is available, will cast to CHROMECAST IP
     ///
@@ -161,51 +668,749 @@ pub enum CastCommand {
         /// Specify the feed type to cast. Will default to supplied
         /// team's applicable Home / Away feed
         feed_type: Option<FeedType>,
-        #[structopt(long, parse(try_from_str))]
-        /// Proxy server address to be passed to Streamlink
-        proxy: Option<Uri>,
     },
 }
 
 #[derive(StructOpt, Debug, PartialEq, Clone)]
 pub enum GenerateCommand {
-    #[structopt(usage = "lazystream generate playlist <FILE> [OPTIONS]")]
+    #[structopt(
+        usage = "lazystream generate playlist <FILE> [--group-by-game --split-by feed --continue-numbering] [OPTIONS]"
+    )]
     /// Generate a .m3u playlist file for all games
     Playlist {
         #[structopt(name = "FILE", parse(from_os_str))]
-        /// File path to save .m3u output
+        /// File path to save .m3u output, or '-' to write it to stdout. The .m3u extension
+        /// is added if FILE doesn't have one, so ie. '.m3u8' can be used instead
         file: PathBuf,
+        #[structopt(long)]
+        /// Group each game's feeds together with a group-title of the teams playing, for
+        /// apps that show groups as folders
+        group_by_game: bool,
+        #[structopt(long, parse(try_from_str), value_name = "SPLIT")]
+        /// Write a separate playlist for each feed type with 'feed', ie. FILE-home.m3u and
+        /// FILE-national.m3u, instead of one combined playlist
+        split_by: Option<SplitBy>,
+        #[structopt(long, requires = "split-by")]
+        /// Continue channel numbers from one split playlist to the next, instead of
+        /// starting each one at the same number
+        continue_numbering: bool,
     },
-    #[structopt(usage = "lazystream generate xmltv <FILE> [--start-channel INT] [OPTIONS]")]
+    #[structopt(
+        usage = "lazystream generate xmltv <FILE> [--start-channel INT --duration MINS --pre-pad DURATION --post-pad DURATION --channel-icon URL --xmltv-dialect DIALECT --download-icons DIR] [OPTIONS]"
+    )]
     /// Generate a .xml XMLTV file for all games with corresponding .m3u playlist file
     Xmltv {
+        #[structopt(name = "FILE", parse(from_os_str))]
+        /// File path to save output, will save both .m3u and .xml files. An extension other
+        /// than .xml, ie. '.m3u8', is kept for the playlist
+        file: PathBuf,
+        #[structopt(flatten)]
+        xmltv: XmltvOptions,
+    },
+    #[structopt(usage = "lazystream generate strm <DIR> [--nfo] [OPTIONS]")]
+    /// Generate a .strm file for each stream, for importing into a Kodi library
+    Strm {
+        #[structopt(name = "DIR", parse(from_os_str))]
+        /// Directory to save .strm files to, created if it doesn't exist
+        dir: PathBuf,
+        #[structopt(long)]
+        /// Also write a Kodi .nfo metadata file next to each .strm file
+        nfo: bool,
+    },
+    #[structopt(
+        usage = "lazystream generate favorites <FILE> [TEAM]... [XMLTV OPTIONS] [OPTIONS]"
+    )]
+    /// Generate a .m3u playlist and .xml XMLTV file of only your favorite teams' games
+    ///
+    /// Example: 'lazystream generate favorites fav Bruins VGK'. Teams default to the
+    /// favorites list in the config file, and the xmltv options are the same as for the
+    /// xmltv subcommand.
+    Favorites {
         #[structopt(name = "FILE", parse(from_os_str))]
         /// File path to save output, will save both .m3u and .xml files
         file: PathBuf,
-        #[structopt(long, default_value = "1000")]
-        /// Specify the starting channel number for the XMLVTV output
-        start_channel: u32,
+        #[structopt(name = "TEAM")]
+        /// Team names or abbreviations, matched the same way as --team
+        teams: Vec<String>,
+        #[structopt(flatten)]
+        xmltv: XmltvOptions,
     },
+    #[structopt(
+        usage = "lazystream generate files <NAME> [--formats FORMATS --nfo] [XMLTV OPTIONS] [OPTIONS]"
+    )]
+    /// Generate any combination of outputs at once, from the same resolved links
+    ///
+    /// Example: 'lazystream generate files nhl --formats m3u,xml,strm' saves nhl.m3u,
+    /// nhl.xml and a directory nhl of .strm files. The xmltv options are the same as
+    /// for the xmltv subcommand.
+    Files {
+        #[structopt(name = "NAME", parse(from_os_str))]
+        /// File path to save output to, without an extension. Used as the directory for
+        /// .strm files
+        name: PathBuf,
+        #[structopt(
+            long,
+            parse(try_from_str),
+            default_value = "m3u,xml",
+            use_delimiter = true,
+            value_name = "FORMATS"
+        )]
+        /// Comma separated outputs to generate: 'm3u', 'xml' and 'strm'
+        formats: Vec<OutputFormat>,
+        #[structopt(long)]
+        /// Also write a Kodi .nfo metadata file next to each .strm file
+        nfo: bool,
+        #[structopt(flatten)]
+        xmltv: XmltvOptions,
+    },
+}
+
+/// Options for the XMLTV guide
+#[derive(StructOpt, Debug, PartialEq, Clone)]
+pub struct XmltvOptions {
+    #[structopt(long, default_value = "1000")]
+    /// Specify the starting channel number for the XMLVTV output
+    pub start_channel: u32,
+    #[structopt(long, default_value = "210", value_name = "MINS")]
+    /// Specify how long each programme lasts from the start of the game
+    pub duration: u32,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_duration),
+        default_value = "0m",
+        value_name = "DURATION"
+    )]
+    /// Start each programme this long before the game starts, ie. '5m'
+    pub pre_pad: Duration,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_duration),
+        default_value = "0m",
+        value_name = "DURATION"
+    )]
+    /// End each programme this long after --duration, ie. '15m' or '1h'. Overtime can
+    /// run long, so PVRs recording from the guide may want extra time
+    pub post_pad: Duration,
+    #[structopt(long, value_name = "URL")]
+    /// Icon to use for every channel. Defaults to the game's thumbnail, or the NHL logo
+    /// if there isn't one
+    pub channel_icon: Option<String>,
+    #[structopt(long, parse(try_from_str), default_value = XmltvDialect::Default.into())]
+    /// Which app or PVR backend the guide is for: 'default', 'plex', 'kodi' or
+    /// 'tvheadend'. 'kodi' and 'tvheadend' add a Sports category to each programme, and
+    /// 'plex' also marks them as new airings so they can be recorded
+    pub xmltv_dialect: XmltvDialect,
+    #[structopt(long, parse(from_os_str), value_name = "DIR")]
+    /// Download each icon once to DIR, relative to --out-dir, and point the guide at
+    /// the local copies so artwork doesn't depend on the NHL's servers. Icons already
+    /// in DIR aren't downloaded again
+    pub download_icons: Option<PathBuf>,
+}
+
+/// Options shared by all generate subcommands
+#[derive(StructOpt, Debug, PartialEq, Clone)]
+pub struct GenerateOptions {
+    #[structopt(long, global = true)]
+    /// Check each link loads before adding it, dropping any that don't
+    pub verify: bool,
+    #[structopt(long, global = true)]
+    /// Keep streams of a game that have the same link as another of its streams separate,
+    /// instead of merging them into one entry labeled with both feeds, ie. 'HOME/AWAY'
+    pub no_dedupe: bool,
+    #[structopt(long, global = true)]
+    /// Don't show progress while resolving links. Progress is only shown in a terminal
+    pub quiet: bool,
+    #[structopt(long, global = true)]
+    /// Don't resolve any stream links, using placeholder links instead. Useful for checking
+    /// the output format quickly
+    pub dry_run: bool,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_date_range),
+        value_name = "START..END",
+        global = true,
+        allow_hyphen_values = true
+    )]
+    /// Include games from every date in the range, inclusive, ie. '20200110..20200116' or
+    /// 'today..+6'. Used instead of --date
+    pub date_range: Option<DateRange>,
+    #[structopt(long, value_name = "N", conflicts_with = "date-range", global = true)]
+    /// Include games from today and the next N days, for a forward looking guide. Unlike
+    /// --date-range, the days roll forward each time --watch regenerates the output.
+    /// Streams of upcoming games that aren't available yet get their unresolved link as a
+    /// placeholder, which starts working once the game is live. Games without any feeds
+    /// listed yet are left out until a later regeneration
+    pub days: Option<u32>,
+    #[structopt(long, parse(from_os_str), value_name = "DIR", global = true)]
+    /// Directory to save output files in, created if it doesn't exist. Defaults to the
+    /// current directory
+    pub out_dir: Option<PathBuf>,
+    #[structopt(long, global = true)]
+    /// Keep running and regenerate the output every day at the --watch-at time, for
+    /// keeping an IPTV server's playlist up to date
+    pub watch: bool,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_time),
+        default_value = "06:00",
+        value_name = "HH:MM",
+        global = true
+    )]
+    /// Local time of day to regenerate the output at when using --watch
+    pub watch_at: NaiveTime,
+    #[structopt(
+        long,
+        parse(try_from_str),
+        default_value = PlaylistFormat::Default.into(),
+        global = true
+    )]
+    /// Which #EXTINF attributes to write to the .m3u playlist, for the app or PVR backend
+    /// it's used with: 'default', 'tvheadend', 'kodi' or 'plex'
+    pub playlist_format: PlaylistFormat,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_channel_step),
+        default_value = "1",
+        value_name = "N",
+        global = true
+    )]
+    /// Number channels N apart, ie. '10' numbers them 1000, 1010, 1020... to leave room
+    /// for channels from other playlists
+    pub channel_step: u32,
+    #[structopt(
+        long,
+        parse(try_from_str),
+        default_value = DEFAULT_TITLE_TEMPLATE,
+        value_name = "TEMPLATE",
+        global = true
+    )]
+    /// Title of each playlist entry. Can use the placeholders '{time}', '{date}', '{away}',
+    /// '{home}', '{feed}' and '{sport}'. Not used for xmltv, where titles match the channel
+    pub title_template: TitleTemplate,
+    #[structopt(long, value_name = "N", global = true)]
+    /// Only include the first N games by start time
+    pub limit: Option<usize>,
+    #[structopt(long, global = true)]
+    /// Only include games broadcast nationally, ie. on ESPN or NBCSN
+    pub national_only: bool,
+    #[structopt(
+        long,
+        parse(try_from_str),
+        default_value = LineEnding::Lf.into(),
+        global = true
+    )]
+    /// Line endings of the .m3u and .xml files: 'lf' or 'crlf'. Some embedded PVR boxes and
+    /// Windows based IPTV players only read playlists with 'crlf' line endings
+    pub line_endings: LineEnding,
+    #[structopt(long, global = true)]
+    /// Start the .m3u and .xml files with a UTF-8 byte order mark, for readers that
+    /// otherwise mangle accented team names like Montréal
+    pub bom: bool,
+}
+
+pub const DEFAULT_TITLE_TEMPLATE: &str = "{time} {away} @ {home} {feed}";
+const TITLE_PLACEHOLDERS: &[&str] = &["time", "date", "away", "home", "feed", "sport"];
+
+/// Title with placeholders, ie. '{time} {away} @ {home} {feed}'
+#[derive(Debug, PartialEq, Clone)]
+pub struct TitleTemplate(String);
+
+impl TitleTemplate {
+    /// Replace each placeholder with its value
+    pub fn render(&self, values: &[(&str, String)]) -> String {
+        let mut title = self.0.clone();
+        for (placeholder, value) in values {
+            title = title.replace(&format!("{{{}}}", placeholder), value);
+        }
+        title
+    }
+}
+
+impl FromStr for TitleTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TitleTemplate, Error> {
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => bail!(
+                    "Placeholder at '{}' is missing a closing '}}'",
+                    &rest[start..]
+                ),
+            };
+
+            let placeholder = &rest[start + 1..end];
+            if !TITLE_PLACEHOLDERS.contains(&placeholder) {
+                bail!(
+                    "Unknown placeholder '{{{}}}', must be one of: {}",
+                    placeholder,
+                    TITLE_PLACEHOLDERS
+                        .iter()
+                        .map(|placeholder| format!("'{{{}}}'", placeholder))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        Ok(TitleTemplate(s.to_string()))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PlaylistFormat {
+    Default,
+    Tvheadend,
+    Kodi,
+    Plex,
+}
+
+impl From<PlaylistFormat> for &str {
+    fn from(format: PlaylistFormat) -> &'static str {
+        match format {
+            PlaylistFormat::Default => "default",
+            PlaylistFormat::Tvheadend => "tvheadend",
+            PlaylistFormat::Kodi => "kodi",
+            PlaylistFormat::Plex => "plex",
+        }
+    }
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PlaylistFormat, Error> {
+        match s {
+            "default" => Ok(PlaylistFormat::Default),
+            "tvheadend" => Ok(PlaylistFormat::Tvheadend),
+            "kodi" => Ok(PlaylistFormat::Kodi),
+            "plex" => Ok(PlaylistFormat::Plex),
+            _ => bail!("Option must match 'default', 'tvheadend', 'kodi' or 'plex'"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SplitBy {
+    Feed,
+}
+
+impl From<SplitBy> for &str {
+    fn from(split_by: SplitBy) -> &'static str {
+        match split_by {
+            SplitBy::Feed => "feed",
+        }
+    }
+}
+
+impl FromStr for SplitBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SplitBy, Error> {
+        match s {
+            "feed" => Ok(SplitBy::Feed),
+            _ => bail!("Option must match 'feed'"),
+        }
+    }
+}
+
+impl std::fmt::Display for SplitBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &str = self.clone().into();
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl From<LineEnding> for &str {
+    fn from(line_ending: LineEnding) -> &'static str {
+        match line_ending {
+            LineEnding::Lf => "lf",
+            LineEnding::Crlf => "crlf",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<LineEnding, Error> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => bail!("Option must match 'lf' or 'crlf'"),
+        }
+    }
+}
+
+impl std::fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &str = self.clone().into();
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum OutputFormat {
+    M3u,
+    Xml,
+    Strm,
+}
+
+impl From<OutputFormat> for &str {
+    fn from(format: OutputFormat) -> &'static str {
+        match format {
+            OutputFormat::M3u => "m3u",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Strm => "strm",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat, Error> {
+        match s {
+            "m3u" => Ok(OutputFormat::M3u),
+            "xml" => Ok(OutputFormat::Xml),
+            "strm" => Ok(OutputFormat::Strm),
+            _ => bail!("Option must match 'm3u', 'xml' or 'strm'"),
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &str = self.clone().into();
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum XmltvDialect {
+    Default,
+    Plex,
+    Kodi,
+    Tvheadend,
+}
+
+impl From<XmltvDialect> for &str {
+    fn from(dialect: XmltvDialect) -> &'static str {
+        match dialect {
+            XmltvDialect::Default => "default",
+            XmltvDialect::Plex => "plex",
+            XmltvDialect::Kodi => "kodi",
+            XmltvDialect::Tvheadend => "tvheadend",
+        }
+    }
+}
+
+impl FromStr for XmltvDialect {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<XmltvDialect, Error> {
+        match s {
+            "default" => Ok(XmltvDialect::Default),
+            "plex" => Ok(XmltvDialect::Plex),
+            "kodi" => Ok(XmltvDialect::Kodi),
+            "tvheadend" => Ok(XmltvDialect::Tvheadend),
+            _ => bail!("Option must match 'default', 'plex', 'kodi' or 'tvheadend'"),
+        }
+    }
+}
+
+impl std::fmt::Display for XmltvDialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &str = self.clone().into();
+        write!(f, "{}", s)
+    }
+}
+
+impl std::fmt::Display for PlaylistFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &str = self.clone().into();
+        write!(f, "{}", s)
+    }
+}
+
+/// Away and home team abbreviations, ie. 'NYR@BOS'
+#[derive(Debug, PartialEq, Clone)]
+pub struct Matchup {
+    pub away: String,
+    pub home: String,
+}
+
+impl FromStr for Matchup {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Matchup, Error> {
+        let mut teams = s.splitn(2, '@').map(|team| team.trim().to_uppercase());
+        match (teams.next(), teams.next()) {
+            (Some(away), Some(home)) if !away.is_empty() && !home.is_empty() => {
+                Ok(Matchup { away, home })
+            }
+            _ => bail!("Must be away and home team abbreviations separated by '@', ie. 'NYR@BOS'"),
+        }
+    }
+}
+
+impl std::fmt::Display for Matchup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} @ {}", self.away, self.home)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    /// Every date in the range, inclusive
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        let mut dates = vec![];
+        let mut date = self.start;
+        while date <= self.end {
+            dates.push(date);
+            date = date.succ();
+        }
+        dates
+    }
 }
 
 pub enum OutputType {
     Generate(Opt),
+    Watch(Opt),
     Select(Opt),
     Record(Opt),
     Cast(Opt),
+    Json(Opt),
+    Qualities(Opt),
+    Doctor(Opt),
+    Inspect(Opt),
 }
 
-fn parse_date(src: &str) -> Result<NaiveDate, ParseError> {
+impl OutputType {
+    pub fn opts(&self) -> &Opt {
+        match self {
+            OutputType::Generate(opts)
+            | OutputType::Watch(opts)
+            | OutputType::Select(opts)
+            | OutputType::Record(opts)
+            | OutputType::Cast(opts)
+            | OutputType::Json(opts)
+            | OutputType::Qualities(opts)
+            | OutputType::Doctor(opts)
+            | OutputType::Inspect(opts) => opts,
+        }
+    }
+}
+
+fn parse_date(src: &str) -> Result<NaiveDate, Error> {
+    let today = Local::today().naive_local();
+
+    match src.to_lowercase().as_str() {
+        "today" => return Ok(today),
+        "yesterday" => return Ok(today.pred()),
+        "tomorrow" => return Ok(today.succ()),
+        _ => {}
+    }
+
+    if src.starts_with('+') || src.starts_with('-') {
+        let offset = src
+            .parse::<i64>()
+            .map_err(|_| format_err!("Day offset must be a number of days, ie. '-2' or '+1'"))?;
+        let date = i32::try_from(offset)
+            .ok()
+            .and_then(|offset| today.checked_add_signed(Duration::days(offset.into())));
+        return match date {
+            Some(date) => Ok(date),
+            None => bail!("Day offset '{}' is too large", src),
+        };
+    }
+
     let s = src.replace("-", "");
-    NaiveDate::parse_from_str(&s, "%Y%m%d")
+    NaiveDate::parse_from_str(&s, "%Y%m%d").map_err(|_| {
+        format_err!(
+            "Must be a date formatted as YYYYMMDD, 'today', 'yesterday', 'tomorrow' \
+             or a day offset, ie. '-2' or '+1'"
+        )
+    })
+}
+
+fn parse_time(src: &str) -> Result<NaiveTime, Error> {
+    NaiveTime::parse_from_str(src, "%H:%M")
+        .map_err(|_| format_err!("Must be a time formatted as HH:MM, ie. '06:00'"))
+}
+
+/// Duration made of hours, minutes and seconds, ie. '1h30m' or '90s'
+fn parse_duration(src: &str) -> Result<Duration, Error> {
+    let invalid = || format_err!("Must be a duration like '10m', '1h' or '1h30m'");
+
+    let too_large = || format_err!("Duration '{}' is too large", src);
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in src.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        if number.is_empty() {
+            return Err(invalid());
+        }
+        let amount = number.parse::<i64>().map_err(|_| too_large())?;
+        let unit = match c.to_ascii_lowercase() {
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        seconds = amount
+            .checked_mul(unit)
+            .and_then(|amount| seconds.checked_add(amount))
+            .ok_or_else(too_large)?;
+        number.clear();
+    }
+
+    if !number.is_empty() || src.trim().is_empty() {
+        return Err(invalid());
+    }
+
+    // Durations are added to game times, so keep them within a range that can't overflow
+    if seconds > MAX_DURATION_SECS {
+        return Err(too_large());
+    }
+
+    Ok(Duration::seconds(seconds))
+}
+
+/// Resolution height, ie. '720p' or '720'
+fn parse_max_quality(src: &str) -> Result<u32, Error> {
+    src.trim_end_matches('p')
+        .parse::<u32>()
+        .map_err(|_| format_err!("Must be a resolution, ie. '720p' or '540p'"))
+}
+
+/// Gap between channel numbers, which must be at least 1 for them to be unique
+fn parse_channel_step(src: &str) -> Result<u32, Error> {
+    match src.parse::<u32>() {
+        Ok(step) if step >= 1 => Ok(step),
+        _ => bail!("Must be a number of at least 1, ie. '10'"),
+    }
+}
+
+fn parse_date_range(src: &str) -> Result<DateRange, Error> {
+    let mut dates = src.splitn(2, "..");
+    let start = parse_date(dates.next().unwrap_or_default())?;
+    let end = match dates.next() {
+        Some(end) => parse_date(end)?,
+        None => bail!("Must be a start and end date separated by '..', ie. '20200110..20200116'"),
+    };
+
+    if end < start {
+        bail!("End date must not be before the start date");
+    }
+
+    Ok(DateRange { start, end })
+}
+
+/// Host must be a full url with scheme, trailing slash is removed
+fn parse_host(src: &str) -> Result<String, Error> {
+    let uri = src
+        .parse::<Uri>()
+        .map_err(|_| format_err!("Must be a valid url, ie. 'http://example.com'"))?;
+
+    if uri.scheme_str().is_none() || uri.authority_part().is_none() {
+        bail!("Must be a valid url with a scheme, ie. 'http://example.com'");
+    }
+
+    Ok(src.trim_end_matches('/').to_string())
+}
+
+/// Header formatted as 'Name: Value'
+fn parse_header(src: &str) -> Result<(String, String), Error> {
+    let mut parts = src.splitn(2, ':');
+    let name = parts.next().unwrap_or_default().trim();
+    let value = match parts.next() {
+        Some(value) => value.trim(),
+        None => bail!("Must be a header name and value separated by ':', ie. 'Cookie: a=b'"),
+    };
+
+    if http::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+        bail!("'{}' isn't a valid header name", name);
+    }
+    if http::header::HeaderValue::from_str(value).is_err() {
+        bail!("'{}' isn't a valid header value", value);
+    }
+
+    Ok((name.to_string(), value.to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<ColorChoice> for &str {
+    fn from(color: ColorChoice) -> &'static str {
+        match color {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ColorChoice, Error> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => bail!("Option must match 'auto', 'always' or 'never'"),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+impl std::fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &str = self.clone().into();
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Cdn {
     Akc,
     L3c,
 }
 
+impl Cdn {
+    /// The other CDN to try when this one fails to resolve
+    pub fn fallback(&self) -> Cdn {
+        match self {
+            Cdn::Akc => Cdn::L3c,
+            Cdn::L3c => Cdn::Akc,
+        }
+    }
+}
+
 impl From<Cdn> for &str {
     fn from(cdn: Cdn) -> &'static str {
         match cdn {
@@ -236,6 +1441,8 @@ impl std::fmt::Display for Cdn {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Quality {
+    Best,
+    Worst,
     _720p60,
     _720p,
     _540p,
@@ -249,6 +1456,8 @@ pub enum Quality {
 impl From<Quality> for &str {
     fn from(quality: Quality) -> &'static str {
         match quality {
+            Quality::Best => "best",
+            Quality::Worst => "worst",
             Quality::_720p60 => "72060",
             Quality::_720p => "720",
             Quality::_540p => "540",
@@ -261,11 +1470,51 @@ impl From<Quality> for &str {
     }
 }
 
+impl Quality {
+    /// Value as passed to --quality
+    pub fn arg(&self) -> &'static str {
+        match self {
+            Quality::Best => "best",
+            Quality::Worst => "worst",
+            Quality::_720p60 => "720p60",
+            Quality::_720p => "720p",
+            Quality::_540p => "540p",
+            Quality::_504p => "504p",
+            Quality::_360p => "360p",
+            Quality::_288p => "288p",
+            Quality::_224p => "224p",
+            Quality::_216p => "216p",
+        }
+    }
+
+    /// Vertical resolution of the quality, if it's a fixed one
+    pub fn height(&self) -> Option<u32> {
+        match self {
+            Quality::Best | Quality::Worst => None,
+            Quality::_720p60 | Quality::_720p => Some(720),
+            Quality::_540p => Some(540),
+            Quality::_504p => Some(504),
+            Quality::_360p => Some(360),
+            Quality::_288p => Some(288),
+            Quality::_224p => Some(224),
+            Quality::_216p => Some(216),
+        }
+    }
+}
+
 impl FromStr for Quality {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Quality, Error> {
-        match s {
+        // Accept any case, and a resolution without the 'p', ie. '720'
+        let mut quality = s.trim().to_lowercase();
+        if !quality.is_empty() && quality.chars().all(|c| c.is_ascii_digit()) {
+            quality.push('p');
+        }
+
+        match quality.as_str() {
+            "best" => Ok(Quality::Best),
+            "worst" => Ok(Quality::Worst),
             "720p60" => Ok(Quality::_720p60),
             "720p" => Ok(Quality::_720p),
             "540p" => Ok(Quality::_540p),
@@ -274,8 +1523,15 @@ impl FromStr for Quality {
             "288p" => Ok(Quality::_288p),
             "224p" => Ok(Quality::_224p),
             "216p" => Ok(Quality::_216p),
+            "1080p" | "1080p60" => bail!(
+                "'{}' isn't offered, 720p60 is the highest quality. Must be one of: 'best', \
+                 'worst', '720p60', '720p', '540p', '504p', '360p', '288p', '224p', '216p'",
+                s
+            ),
             _ => bail!(
-                "Must be one of: '720p60', '720p', '540p', '504p', '360p', '288p', '224p', '216p'"
+                "'{}' isn't a quality, must be one of: 'best', 'worst', '720p60', '720p', \
+                 '540p', '504p', '360p', '288p', '224p', '216p'",
+                s
             ),
         }
     }
@@ -313,7 +1569,7 @@ impl FromStr for FeedType {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<FeedType, Error> {
-        match s {
+        match s.to_uppercase().as_str() {
             "HOME" => Ok(FeedType::Home),
             "AWAY" => Ok(FeedType::Away),
             "FRENCH" => Ok(FeedType::French),
@@ -0,0 +1,53 @@
+use crate::{log_error, opt::Opt};
+use async_std::{process, task};
+use failure::Error;
+
+pub fn run(opts: Opt) {
+    task::block_on(async {
+        if let Err(e) = process(opts).await {
+            log_error(&e);
+            process::exit(1);
+        };
+    });
+}
+
+async fn process(opts: Opt) -> Result<(), Error> {
+    let (game, mut stream) = crate::select::process(&opts, true).await?;
+
+    let mut variants = stream.variants(&opts.cdn).await?;
+    variants.sort_by(|a, b| b.bandwidth.cmp(&a.bandwidth));
+
+    println!(
+        "Available qualities for {} @ {} {}...\n",
+        game.away_team.name, game.home_team.name, stream.feed_type
+    );
+    println!(
+        "{:<12}{:>12}{:>12}",
+        "RESOLUTION", "BANDWIDTH", "FRAME RATE"
+    );
+    for variant in variants {
+        println!(
+            "{:<12}{:>12}{:>12}",
+            variant.resolution.unwrap_or_else(|| String::from("-")),
+            format!("{:.1} Mbps", variant.bandwidth as f64 / 1_000_000.0),
+            variant.frame_rate.unwrap_or_else(|| String::from("-")),
+        );
+    }
+
+    let audio_tracks = stream.audio_tracks(&opts.cdn).await?;
+    if !audio_tracks.is_empty() {
+        println!("\nAvailable audio tracks...\n");
+        println!("{:<16}{:<12}{:<12}", "NAME", "GROUP", "LANGUAGE");
+        for track in audio_tracks {
+            println!(
+                "{:<16}{:<12}{:<12}{}",
+                track.name,
+                track.group_id,
+                track.language.unwrap_or_else(|| String::from("-")),
+                if track.default { "(default)" } else { "" },
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,99 +1,845 @@
 use crate::{
-    log_error,
-    opt::{Command, FeedType, Opt},
-    stream::{Game, LazyStream, Stream},
+    format_time, log_error,
+    opt::{Command, FeedType, Matchup, Opt},
+    sanitize_filename, status,
+    stream::{AudioTrack, Game, GameState, LazyStream, Stream},
     BANNER,
 };
 use async_std::{process, task};
-use chrono::Local;
-use failure::Error;
+use colored::Colorize;
+use failure::{bail, format_err, Error, ResultExt};
 use read_input::prelude::*;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Set while a player or ffmpeg is running, which handle Ctrl-C themselves
+static CHILD_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Set if Ctrl-C was pressed while a player or ffmpeg was running
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
 pub fn run(opts: Opt) {
+    // Child processes get the interrupt too, so only exit when none are running
+    let handler = ctrlc::set_handler(|| {
+        if CHILD_RUNNING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            eprintln!("\nCancelled");
+            std::process::exit(130);
+        }
+    });
+    if let Err(e) = handler {
+        log_error(&format_err!("Failed to set Ctrl-C handler: {}", e));
+    }
+
+    let select_opts = SelectOptions::from(&opts.command);
+
     task::block_on(async {
-        if let Err(e) = process(&opts, false).await {
+        let result = if select_opts.list {
+            list(&opts).await
+        } else {
+            process(&opts, false).await.map(|_| ())
+        };
+
+        if let Err(e) = result {
             log_error(&e);
             process::exit(1);
         };
     });
 
-    if cfg!(target_os = "windows") {
+    if cfg!(target_os = "windows") && !select_opts.is_raw() {
         pause();
     }
 }
 
+/// Print the games for the day without prompting
+async fn list(opts: &Opt) -> Result<(), Error> {
+    let lazy_stream = LazyStream::new(opts).await?;
+    let games = lazy_stream.games();
+
+    println!("Games for {}...\n", lazy_stream.date().format("%Y-%m-%d"));
+    print_games(&games, opts);
+
+    Ok(())
+}
+
 pub async fn process(opts: &Opt, need_return: bool) -> Result<(Game, Stream), Error> {
-    println!("{}", BANNER);
+    let select_opts = SelectOptions::from(&opts.command);
+    let raw = !need_return && select_opts.is_raw();
+
+    // Banner goes to stderr so it doesn't end up in captured output
+    if !raw && !opts.no_banner {
+        eprintln!("{}", BANNER);
+    }
 
-    let resolve = if let Command::Select { resolve } = opts.command {
-        resolve
+    // Multiple games / streams can only be picked when just printing links
+    let multiple = !need_return && select_opts.player.is_none() && select_opts.record.is_none();
+
+    let lazy_stream = match select_opts.game_pk {
+        Some(game_pk) => LazyStream::for_game(opts, game_pk).await?,
+        None => LazyStream::new(opts).await?,
+    };
+    let mut games = lazy_stream.games();
+
+    if games.is_empty() && opts.live_only {
+        bail!(
+            "All games on {} have finished, and --live-only was passed",
+            lazy_stream.date().format("%Y-%m-%d")
+        );
+    }
+
+    let mut team_matched = false;
+    if select_opts.game_pk.is_some() {
+        team_matched = true;
+    } else if let Some(matchup) = &select_opts.matchup {
+        lazy_stream.check_team_abbrev(&matchup.away)?;
+        lazy_stream.check_team_abbrev(&matchup.home)?;
+
+        games.retain(|game| {
+            game.away_team.abbreviation == matchup.away
+                && game.home_team.abbreviation == matchup.home
+        });
+        if games.is_empty() {
+            bail!(
+                "No {} game on {}, use --date to specify the day it's played",
+                matchup,
+                lazy_stream.date().format("%Y-%m-%d")
+            );
+        }
+        team_matched = true;
+    } else if let Some(ref team) = opts.team {
+        let matching: Vec<Game> = games
+            .iter()
+            .filter(|game| game.has_team_matching(team))
+            .cloned()
+            .collect();
+
+        if matching.is_empty() {
+            status(
+                raw,
+                &format!(
+                    "\n{}: No games found matching team {}, showing all games",
+                    "WARNING".yellow(),
+                    team
+                ),
+            );
+        } else {
+            games = matching;
+            team_matched = true;
+        }
+    }
+
+    let picked_games = if team_matched && games.len() == 1 {
+        let game = games.remove(0);
+        status(
+            raw,
+            &format!(
+                "\nFound game for {}: {} @ {}",
+                lazy_stream.date().format("%Y-%m-%d"),
+                game.away_team.name,
+                game.home_team.name
+            ),
+        );
+        vec![game]
     } else {
-        false
+        if raw {
+            bail!("Can't pick a game with --raw, use --team, --game-pk or --matchup to pick one");
+        }
+
+        println!(
+            "\nPick a game for {}...\n",
+            lazy_stream.date().format("%Y-%m-%d")
+        );
+        print_games(&games, opts);
+
+        // Default to the game that's on now when just looking at today's games
+        let default = if opts.date.is_none() && opts.team.is_none() {
+            now_playing(&games)
+        } else {
+            None
+        };
+
+        choose(games.len(), multiple, default)
+            .into_iter()
+            .map(|choice| games[choice - 1].clone())
+            .collect()
     };
 
-    let lazy_stream = LazyStream::new(opts).await?;
-    let mut games = lazy_stream.games();
+    let game_count = picked_games.len();
+    let mut selections = vec![];
+    for mut game in picked_games {
+        if !opts.force && !opts.wait {
+            if let Some(warning) = game.availability_warning() {
+                if raw {
+                    bail!("{}, use --force to continue anyway", warning);
+                }
+
+                println!("\n{}: {}", "WARNING".yellow(), warning);
+                if !confirm("\nContinue anyway? (y/n) ") {
+                    bail!("Cancelled, use --force to skip this check");
+                }
+            }
+        }
+
+        let mut streams = game.streams().await?;
+        if streams.is_empty() {
+            if !opts.wait {
+                bail!("No streams available yet, use --wait to keep checking until they are");
+            }
+            streams = wait_for_streams(&mut game, opts, raw).await?;
+        }
+
+        if let Some(description) = game.description().await {
+            if !description.is_empty() {
+                status(raw, &format!("\n{}", description));
+            }
+        }
+
+        let mut feeds: Vec<FeedType> = streams.clone().into_iter().map(|(k, _)| k).collect();
+        feeds.sort();
+
+        if let Some(feed) = &select_opts.feed {
+            if let Some(stream) = streams.remove(feed) {
+                status(raw, &format!("\nUsing stream feed {}", feed));
+                selections.push((game.clone(), stream));
+                continue;
+            }
+
+            let available: Vec<String> = feeds.iter().map(FeedType::to_string).collect();
+            bail!(
+                "No {} feed for {} @ {}, available feeds are: {}",
+                feed,
+                game.away_team.name,
+                game.home_team.name,
+                available.join(", ")
+            );
+        }
+
+        if select_opts.all_feeds && !need_return {
+            for feed in feeds {
+                let stream = streams.remove(&feed).unwrap();
+                selections.push((game.clone(), stream));
+            }
+            continue;
+        }
+
+        if raw {
+            bail!("Can't pick a stream with --raw, use --feed or --all-feeds to pick one");
+        }
+
+        if game_count > 1 {
+            println!(
+                "\nPick a stream for {} @ {}...\n",
+                game.away_team.name, game.home_team.name
+            );
+        } else {
+            println!("\nPick a stream...\n");
+        }
+
+        for (idx, feed_type) in feeds.iter().enumerate() {
+            println!("{}) {}", idx + 1, feed_type);
+        }
+
+        for feed_choice in choose(feeds.len(), multiple, None) {
+            let feed_choice = &feeds[(feed_choice - 1)];
+            let stream = streams.remove(feed_choice).unwrap();
+            selections.push((game.clone(), stream));
+        }
+    }
+
+    status(raw, "");
+    let cdn = &lazy_stream.opts.cdn;
+
+    // With --all-feeds, links are always labeled so the output looks the same even for
+    // a game with only one feed
+    if selections.len() > 1 || (select_opts.all_feeds && !need_return) {
+        for (game, stream) in selections.iter_mut() {
+            let link = selection_link(&select_opts, &lazy_stream.opts, stream)
+                .await
+                .unwrap_or_else(|e| format!("Not available, {}", e));
+            println!(
+                "{} @ {} {}: {}",
+                game.away_team.name, game.home_team.name, stream.feed_type, link
+            );
+        }
+
+        return Ok(selections.remove(0));
+    }
+
+    let (game, mut stream) = selections.remove(0);
+    if !need_return {
+        let link = if opts.wait {
+            wait_for_link(&select_opts, &lazy_stream.opts, &mut stream, raw).await?
+        } else {
+            selection_link(&select_opts, &lazy_stream.opts, &mut stream).await?
+        };
+        if let Some(resolved_cdn) = stream.cdn() {
+            opts.remember(resolved_cdn);
+
+            if resolved_cdn != cdn {
+                status(
+                    raw,
+                    &format!(
+                        "Stream not available on {}, using {} instead\n",
+                        cdn, resolved_cdn
+                    ),
+                );
+            }
+        }
+
+        let host = opts.hosts().remove(0);
+        if let Some(resolved_host) = stream.host() {
+            if resolved_host != host {
+                status(
+                    raw,
+                    &format!(
+                        "Stream not available from {}, using {} instead\n",
+                        host, resolved_host
+                    ),
+                );
+            }
+        }
+
+        if !select_opts.quiet {
+            status(raw, &summary(&game, &stream, &lazy_stream.opts));
+        }
+
+        if let Some(player) = select_opts.player {
+            let has_quality = lazy_stream.opts.quality.is_some();
+            let from_start = select_opts.from_start;
+            let audio_track = match &opts.audio_track {
+                Some(name) => Some(audio_track(&mut stream, name, opts).await?),
+                None => None,
+            };
+            task::spawn_blocking(move || {
+                play(
+                    &player,
+                    &link,
+                    has_quality,
+                    from_start,
+                    audio_track.as_ref(),
+                    raw,
+                )
+            })
+            .await?;
+        } else if let Some(mut output) = select_opts.record {
+            if output.is_dir() {
+                let filename = format!(
+                    "{} @ {} {}.mp4",
+                    game.away_team.name,
+                    game.home_team.name,
+                    format_time(game.game_date, opts.timezone, "%Y-%m-%d")
+                );
+                output.push(sanitize_filename(&filename));
+            }
+            let from_start = select_opts.from_start;
+            task::spawn_blocking(move || record(&link, &output, from_start)).await?;
+        } else {
+            if select_opts.from_start {
+                status(
+                    raw,
+                    &format!(
+                        "{}: --from-start only applies with --player or --record\n",
+                        "WARNING".yellow()
+                    ),
+                );
+            }
+            println!("{}", link);
+        }
+    }
+
+    Ok((game, stream))
+}
+
+/// Print the numbered list of games to pick from, aligned into columns
+fn print_games(games: &[Game], opts: &Opt) {
+    let idx_width = games.len().to_string().len();
+    let away_width = games
+        .iter()
+        .map(|game| game.away_team.name.chars().count())
+        .max()
+        .unwrap_or(0);
 
-    println!(
-        "\nPick a game for {}...\n",
-        lazy_stream.date().format("%Y-%m-%d")
-    );
     for (idx, game) in games.iter().enumerate() {
+        let status = match game.status_with_score() {
+            Some(status) if !opts.no_scores => {
+                let status = format!(" ({})", status);
+                match game.status.state() {
+                    GameState::Live => status.green().to_string(),
+                    GameState::Final => status.dimmed().to_string(),
+                    GameState::Preview => status,
+                }
+            }
+            _ => String::new(),
+        };
+        let national = if game.national_broadcasts.is_empty() {
+            String::new()
+        } else {
+            format!(" 📺 {}", game.national_broadcasts.join(", "))
+                .yellow()
+                .to_string()
+        };
+
         println!(
-            "{}) {} - {} @ {}",
+            "{:>idx_width$}) {:>8} - {} @ {}{}{}",
             idx + 1,
-            game.game_date
-                .with_timezone(&Local)
-                .time()
-                .format("%-I:%M %p")
-                .to_string(),
-            game.away_team.name,
-            game.home_team.name
+            format_time(game.game_date, opts.timezone, "%-I:%M %p"),
+            format!(
+                "{:<away_width$}",
+                game.away_team.name,
+                away_width = away_width
+            )
+            .cyan(),
+            game.home_team.name.magenta(),
+            status,
+            national,
+            idx_width = idx_width,
         );
     }
+}
 
-    let game_count = games.len();
-    let game_choice = input::<usize>()
-        .msg("\n>>> ")
-        .add_test(move |input| *input > 0 && *input <= game_count)
-        .get();
-    let mut game = games.remove(game_choice - 1);
+/// The game, feed, CDN and the quality actually resolved, which can differ from the one
+/// requested with 'best', 'worst' or --quality-fallback
+fn summary(game: &Game, stream: &Stream, opts: &Opt) -> String {
+    let cdn = stream.cdn().unwrap_or(&opts.cdn);
 
-    let mut streams = game.streams().await?;
+    let quality = match (&opts.quality, stream.quality_variant()) {
+        (Some(quality), Some(variant)) => {
+            let requested = match opts.max_quality {
+                Some(max_height) => format!("up to {}p", max_height),
+                None => quality.arg().to_string(),
+            };
+            let resolution = variant
+                .resolution
+                .unwrap_or_else(|| String::from("unknown"));
+            match variant.frame_rate {
+                Some(frame_rate) => format!(
+                    "{} at {}fps ({} requested)",
+                    resolution, frame_rate, requested
+                ),
+                None => format!("{} ({} requested)", resolution, requested),
+            }
+        }
+        _ => String::from("adaptive quality"),
+    };
 
-    println!("\nPick a stream...\n");
+    format!(
+        "NHL: {} @ {}, {} feed on {}, {}\n",
+        game.away_team.name, game.home_team.name, stream.feed_type, cdn, quality
+    )
+}
 
-    let mut feeds: Vec<FeedType> = streams.clone().into_iter().map(|(k, _)| k).collect();
-    feeds.sort();
-    for (idx, feed_type) in feeds.iter().enumerate() {
-        println!("{}) {}", idx + 1, feed_type);
+/// Link to output for the stream, resolved if a quality is set or it's needed to play / record
+async fn selection_link(
+    select_opts: &SelectOptions,
+    opts: &Opt,
+    stream: &mut Stream,
+) -> Result<String, Error> {
+    if let Some(ref quality) = opts.quality {
+        stream.quality_link(&opts.cdn, quality).await
+    } else if select_opts.resolve || select_opts.player.is_some() || select_opts.record.is_some() {
+        stream.master_link(&opts.cdn).await
+    } else {
+        Ok(stream.host_link(&opts.cdn))
     }
+}
 
-    let feed_count = feeds.len();
-    let feed_choice = input::<usize>()
-        .msg("\n>>> ")
-        .add_test(move |input| *input > 0 && *input <= feed_count)
-        .get();
-    let feed_choice = &feeds[(feed_choice - 1)];
-    let mut stream = streams.remove(feed_choice).unwrap();
+/// Choice of the game that's currently live, or else the one starting soonest
+fn now_playing(games: &[Game]) -> Option<usize> {
+    let live = games
+        .iter()
+        .position(|game| game.status.state() == GameState::Live);
 
-    let host_link = stream.host_link(&lazy_stream.opts.cdn);
+    let soonest = || {
+        games
+            .iter()
+            .enumerate()
+            .filter(|(_, game)| game.status.state() == GameState::Preview)
+            .min_by_key(|(_, game)| game.game_date)
+            .map(|(idx, _)| idx)
+    };
 
-    println!();
-    let cdn = &lazy_stream.opts.cdn;
-    if !need_return {
-        if let Some(ref quality) = lazy_stream.opts.quality {
-            let quality_link = stream.quality_link(cdn, quality).await?;
-            println!("{}", quality_link);
-        } else if resolve {
-            let master_link = stream.master_link(cdn).await?;
-            println!("{}", master_link);
-        } else {
-            println!("{}", host_link);
+    live.or_else(soonest).map(|idx| idx + 1)
+}
+
+/// Prompt for a choice between 1 and count. If multiple is allowed, any number of
+/// comma or space separated choices can be entered. If there's a default, it's
+/// shown in the prompt and picked when nothing is entered.
+fn choose(count: usize, multiple: bool, default: Option<usize>) -> Vec<usize> {
+    let msg = match default {
+        Some(default) => format!("\n>>> [{}] ", default),
+        None => String::from("\n>>> "),
+    };
+
+    if multiple {
+        let mut builder = input::<String>()
+            .msg(msg)
+            .add_test(move |input| parse_choices(input, count).is_some());
+        if let Some(default) = default {
+            builder = builder.default(default.to_string());
+        }
+        let choices = builder.get();
+        parse_choices(&choices, count).unwrap_or_default()
+    } else {
+        let mut builder = input::<usize>()
+            .msg(msg)
+            .add_test(move |input| *input > 0 && *input <= count);
+        if let Some(default) = default {
+            builder = builder.default(default);
         }
+        vec![builder.get()]
     }
+}
 
-    Ok((game, stream))
+/// Parse comma or space separated choices, all of which must be between 1 and count
+fn parse_choices(input: &str, count: usize) -> Option<Vec<usize>> {
+    let choices = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|choice| !choice.is_empty())
+        .map(|choice| {
+            choice
+                .parse::<usize>()
+                .ok()
+                .filter(|choice| *choice > 0 && *choice <= count)
+        })
+        .collect::<Option<Vec<usize>>>()?;
+
+    let mut unique = vec![];
+    for choice in choices {
+        if !unique.contains(&choice) {
+            unique.push(choice);
+        }
+    }
+
+    if unique.is_empty() {
+        None
+    } else {
+        Some(unique)
+    }
+}
+
+/// Check for streams every wait interval until they're available or wait timeout is hit
+async fn wait_for_streams(
+    game: &mut Game,
+    opts: &Opt,
+    raw: bool,
+) -> Result<HashMap<FeedType, Stream>, Error> {
+    let interval = Duration::from_secs(opts.wait_interval);
+    let timeout = Duration::from_secs(opts.wait_timeout * 60);
+    let start = Instant::now();
+
+    let mut attempt = 1;
+    while start.elapsed() < timeout {
+        status(
+            raw,
+            &format!(
+                "Streams not available yet, checking again in {}s (attempt {})...",
+                opts.wait_interval, attempt
+            ),
+        );
+        task::sleep(interval).await;
+
+        game.refresh();
+        if let Ok(streams) = game.streams().await {
+            if !streams.is_empty() {
+                return Ok(streams);
+            }
+        }
+        attempt += 1;
+    }
+
+    bail!(
+        "Streams still not available after waiting {} minutes",
+        opts.wait_timeout
+    );
+}
+
+/// Check for the stream's link every wait interval until it resolves or wait timeout is
+/// hit. The feed can be listed a while before the host and CDN are serving it.
+async fn wait_for_link(
+    select_opts: &SelectOptions,
+    opts: &Opt,
+    stream: &mut Stream,
+    raw: bool,
+) -> Result<String, Error> {
+    let interval = Duration::from_secs(opts.wait_interval);
+    let timeout = Duration::from_secs(opts.wait_timeout * 60);
+    let start = Instant::now();
+
+    let mut attempt = 1;
+    loop {
+        let error = match selection_link(select_opts, opts, stream).await {
+            Ok(link) => return Ok(link),
+            Err(e) => e,
+        };
+
+        if start.elapsed() >= timeout {
+            bail!(
+                "Stream link still not available after waiting {} minutes, {}",
+                opts.wait_timeout,
+                error
+            );
+        }
+
+        status(
+            raw,
+            &format!(
+                "Stream is listed but its link isn't ready yet ({}), checking again in {}s \
+                 (attempt {})...",
+                error, opts.wait_interval, attempt
+            ),
+        );
+        task::sleep(interval).await;
+
+        stream.reset_links();
+        attempt += 1;
+    }
+}
+
+/// Options specific to the select subcommand, defaulted when the selection
+/// is being done for another command
+#[derive(Default)]
+struct SelectOptions {
+    resolve: bool,
+    player: Option<String>,
+    record: Option<PathBuf>,
+    list: bool,
+    feed: Option<FeedType>,
+    all_feeds: bool,
+    from_start: bool,
+    game_pk: Option<u64>,
+    matchup: Option<Matchup>,
+    quiet: bool,
+    raw: bool,
+}
+
+impl SelectOptions {
+    /// Only print the link to stdout, if --raw was passed or stdin isn't a terminal, since
+    /// prompts can't be answered then
+    fn is_raw(&self) -> bool {
+        self.raw || !atty::is(atty::Stream::Stdin)
+    }
+}
+
+impl From<&Command> for SelectOptions {
+    fn from(cmd: &Command) -> Self {
+        match cmd {
+            Command::Select {
+                resolve,
+                player,
+                record,
+                list,
+                feed,
+                all_feeds,
+                from_start,
+                game_pk,
+                matchup,
+                quiet,
+                raw,
+            } => SelectOptions {
+                resolve: *resolve,
+                player: player.clone(),
+                record: record.clone(),
+                list: *list,
+                feed: feed.clone(),
+                all_feeds: *all_feeds,
+                from_start: *from_start,
+                game_pk: *game_pk,
+                matchup: matchup.clone(),
+                quiet: *quiet,
+                raw: *raw,
+            },
+            _ => SelectOptions::default(),
+        }
+    }
+}
+
+/// Find the stream's audio track matching name or language
+async fn audio_track(stream: &mut Stream, name: &str, opts: &Opt) -> Result<AudioTrack, Error> {
+    if opts.quality.is_some() {
+        bail!(
+            "--audio-track can't be used with --quality, only the adaptive stream has audio tracks"
+        );
+    }
+
+    let tracks = stream.audio_tracks(&opts.cdn).await?;
+    let matching = tracks.iter().find(|track| {
+        track.name.eq_ignore_ascii_case(name)
+            || track
+                .language
+                .as_ref()
+                .map_or(false, |language| language.eq_ignore_ascii_case(name))
+    });
+
+    match matching {
+        Some(track) => Ok(track.clone()),
+        None if tracks.is_empty() => bail!("Stream doesn't have any alternate audio tracks"),
+        None => {
+            let names: Vec<&str> = tracks.iter().map(|track| track.name.as_str()).collect();
+            bail!(
+                "No audio track matching {}, available tracks are: {}",
+                name,
+                names.join(", ")
+            );
+        }
+    }
+}
+
+/// Open the link with the player command, passing along any arguments supplied with it.
+///
+/// The player is waited on with std's Command from spawn_blocking, since the
+/// async_std::process of async-std 1.4 has no Command. It was added in 1.7, which needs a
+/// newer Rust than CI builds with.
+fn play(
+    player: &str,
+    link: &str,
+    has_quality: bool,
+    from_start: bool,
+    audio_track: Option<&AudioTrack>,
+    raw: bool,
+) -> Result<(), Error> {
+    let mut parts = player.split_whitespace();
+    let cmd = parts
+        .next()
+        .ok_or_else(|| format_err!("Player command is empty"))?;
+
+    let mut args: Vec<&str> = parts.collect();
+
+    let player_name = Path::new(cmd)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let is_streamlink = player_name == "streamlink";
+
+    if from_start {
+        match player_name.as_str() {
+            "streamlink" => args.push("--hls-live-restart"),
+            "mpv" => args.push("--demuxer-lavf-o=live_start_index=0"),
+            _ => status(
+                raw,
+                &format!(
+                    "{}: --from-start isn't supported with {}, starting at the live edge",
+                    "WARNING".yellow(),
+                    cmd
+                ),
+            ),
+        }
+    }
+
+    let audio_arg;
+    if let Some(track) = audio_track {
+        match (player_name.as_str(), &track.language) {
+            ("streamlink", _) => {
+                args.push("--hls-audio-select");
+                args.push(&track.name);
+            }
+            ("mpv", Some(language)) => {
+                audio_arg = format!("--alang={}", language);
+                args.push(&audio_arg);
+            }
+            _ => status(
+                raw,
+                &format!(
+                    "{}: --audio-track isn't supported with {}, using the default audio",
+                    "WARNING".yellow(),
+                    cmd
+                ),
+            ),
+        }
+    }
+
+    args.push(link);
+
+    // Streamlink needs a stream name, default to best when adaptive
+    if is_streamlink && !has_quality {
+        args.push("best");
+    }
+
+    status(raw, &format!("Opening stream with {}...", cmd));
+
+    CHILD_RUNNING.store(true, Ordering::SeqCst);
+    let status = std::process::Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context(format_err!(
+            "Could not run {}. Please ensure it is installed and accessible from your PATH",
+            cmd
+        ));
+    CHILD_RUNNING.store(false, Ordering::SeqCst);
+    let status = status?;
+
+    if !status.success() && !INTERRUPTED.load(Ordering::SeqCst) {
+        bail!("Player exited with {}", status);
+    }
+
+    Ok(())
+}
+
+/// Record the link to output with ffmpeg, until the stream ends or is interrupted.
+///
+/// Run from spawn_blocking with std's Command for the same reason as play.
+fn record(link: &str, output: &PathBuf, from_start: bool) -> Result<(), Error> {
+    let cmd = if cfg!(target_os = "windows") {
+        "ffmpeg.exe"
+    } else {
+        "ffmpeg"
+    };
+
+    println!(
+        "Recording to {:?} with ffmpeg, press Ctrl-C to stop...\n\n============================\n",
+        output
+    );
+
+    // Start from the oldest segment in the playlist rather than the live edge
+    let start_index = if from_start { "0" } else { "-3" };
+
+    // ffmpeg receives the interrupt too and finalizes the file before exiting,
+    // so we just need to stay alive until it's done
+    CHILD_RUNNING.store(true, Ordering::SeqCst);
+    let status = std::process::Command::new(cmd)
+        .args(&["-live_start_index", start_index])
+        .arg("-i")
+        .arg(link)
+        .args(&["-c", "copy"])
+        .arg(output)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context(format_err!(
+            "Could not run ffmpeg. Please ensure it is installed and accessible from your PATH"
+        ));
+    CHILD_RUNNING.store(false, Ordering::SeqCst);
+    let status = status?;
+
+    if !status.success() && !INTERRUPTED.load(Ordering::SeqCst) {
+        bail!("ffmpeg exited with {}", status);
+    }
+
+    println!(
+        "\n============================\n\nRecording saved to: {:?}",
+        output
+    );
+
+    Ok(())
+}
+
+/// Ask for a yes / no answer
+fn confirm(msg: &str) -> bool {
+    let answer = input::<String>()
+        .msg(msg)
+        .add_test(|answer| ["y", "yes", "n", "no"].contains(&answer.to_lowercase().as_str()))
+        .get();
+
+    answer.to_lowercase().starts_with('y')
 }
 
 // Keep console window open until button press
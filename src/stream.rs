@@ -1,62 +1,167 @@
 use crate::{
-    opt::{Cdn, FeedType, Opt, Quality},
-    HOST,
+    opt::{Cdn, Command, FeedType, Opt, Quality},
+    VERSION,
 };
-use chrono::{DateTime, Local, NaiveDate, Utc};
+use async_std::{fs, task};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
 use failure::{bail, format_err, Error, ResultExt};
-use futures::{future, AsyncReadExt};
+use futures::{
+    future::{self, BoxFuture},
+    AsyncReadExt, Future, FutureExt, StreamExt,
+};
 use http_client::{native::NativeClient, Body, HttpClient};
-use stats_api::{
-    model::nhl::{GameContentArticleMediaImageCut, GameContentResponse, Team},
-    NhlClient,
+use isahc::prelude::RequestBuilderExt;
+use log::{debug, info, warn};
+use serde::{de::DeserializeOwned, Deserialize};
+use stats_api::model::nhl::{GameContentArticleMediaImageCut, GameContentResponse, Team};
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use std::{collections::HashMap, str::FromStr};
+use url::Url;
+
+/// Hours from the start of a finished game until its stream links are expected to stop working
+const FINAL_STREAM_HOURS: i64 = 12;
+
+/// Longest wait between retries, which otherwise double each attempt
+const MAX_BACKOFF_MS: u64 = 30_000;
+
+/// Base url of the NHL stats api. Requests are made here rather than with stats-api's
+/// client, so they go through the client's transport
+const STATS_API: &str = "https://statsapi.web.nhl.com/api/v1";
+
+const STATS_API_OFFLINE: &str = "Could not get data from the NHL stats api, it may be offline";
+
+/// Extra details included with each game of the schedule
+const SCHEDULE_HYDRATE: &str = "broadcasts,venue,linescore";
+
+/// How long a cached schedule is used before it's fetched again, unless --refresh is passed
+const SCHEDULE_CACHE_SECS: u64 = 10 * 60;
+
+/// Called with the number of games resolved so far and the total
+type Progress = Box<dyn Fn(usize, usize)>;
 
 pub struct LazyStream {
     pub opts: Opt,
     games: Vec<Game>,
     teams: Vec<Team>,
+    progress: Option<Progress>,
 }
 
 impl LazyStream {
     pub async fn new(opts: &Opt) -> Result<Self, Error> {
+        LazyStream::with_client(opts, Client::new(opts)).await
+    }
+
+    /// Get the games using an existing client, so its connections are reused
+    pub async fn with_client(opts: &Opt, client: Client) -> Result<Self, Error> {
         let date = if opts.date.is_some() {
             opts.date.clone().unwrap()
         } else {
             Local::today().naive_local()
         };
 
-        let client = NhlClient::new();
-        let schedule = client.get_schedule_for(date).await?;
-        let teams = client.get_teams().await?;
+        debug!("Getting schedule for {} from the stats api", date);
+        let schedule = get_schedule_for(&client, date, opts).await?;
+        let teams = get_teams(&client, opts.retries).await?;
+
+        LazyStream::from_schedule(opts.clone(), schedule, teams, &client)
+    }
+
+    /// Get a single game by its id, looked up directly instead of from the schedule of
+    /// --date, so it can be played on any day
+    pub async fn for_game(opts: &Opt, game_pk: u64) -> Result<Self, Error> {
+        LazyStream::for_game_with_client(opts, Client::new(opts), game_pk).await
+    }
+
+    /// Get a single game by its id using an existing client
+    pub async fn for_game_with_client(
+        opts: &Opt,
+        client: Client,
+        game_pk: u64,
+    ) -> Result<Self, Error> {
+        debug!("Getting game {} from the stats api", game_pk);
+        let query = format!("gamePk={}", game_pk);
+        let schedule = get_schedule(&client, &query, opts.retries).await?;
+
+        let date = schedule
+            .dates
+            .iter()
+            .find(|date| date.games.iter().any(|game| game.game_pk == game_pk));
+        let date = match date {
+            Some(date) => parse_schedule_date(&date.date)?,
+            None => bail!("Game {} not found", game_pk),
+        };
+        let teams = get_teams(&client, opts.retries).await?;
+
+        // Messages about the day of the game should show the day it's played on
+        let mut opts = opts.clone();
+        opts.date = Some(date);
 
+        LazyStream::from_schedule(opts, schedule, teams, &client)
+    }
+
+    /// Build the games of the schedule, matched up with their teams
+    fn from_schedule(
+        opts: Opt,
+        schedule: Schedule,
+        teams: Vec<Team>,
+        client: &Client,
+    ) -> Result<Self, Error> {
         let mut games = vec![];
-        for game in schedule.games {
-            let game_pk = game.game_pk;
-            let game_date = game.date;
-            let home_team = teams
-                .iter()
-                .find(|team| team.id == game.teams.home.detail.id)
-                .unwrap();
-            let away_team = teams
-                .iter()
-                .find(|team| team.id == game.teams.away.detail.id)
-                .unwrap();
+        for schedule_date in schedule.dates {
+            let date = parse_schedule_date(&schedule_date.date)?;
 
-            let game = Game::new(
-                game_pk,
-                game_date,
-                date,
-                home_team.clone(),
-                away_team.clone(),
-            );
-            games.push(game);
+            for game in schedule_date.games {
+                let game_pk = game.game_pk;
+                let game_date = game
+                    .game_date
+                    .parse::<DateTime<Utc>>()
+                    .context(format_err!("Invalid start time of game {}", game_pk))?;
+                let team = |id: u64| {
+                    teams
+                        .iter()
+                        .find(|team| team.id == id)
+                        .cloned()
+                        .ok_or_else(|| format_err!("Team {} of game {} not found", id, game_pk))
+                };
+                let home_team = team(game.teams.home.team.id)?;
+                let away_team = team(game.teams.away.team.id)?;
+
+                let game_venue = game.venue.map(|venue| venue.name);
+                let status = GameStatus {
+                    detailed_state: game.status.detailed_state,
+                    away_score: game.teams.away.score.to_string(),
+                    home_score: game.teams.home.score.to_string(),
+                    progress: game.linescore.and_then(|linescore| linescore.progress()),
+                };
+                if opts.live_only && status.state() == GameState::Final {
+                    continue;
+                }
+
+                let mut national_broadcasts: Vec<String> = vec![];
+                for broadcast in game.broadcasts.iter().filter(|b| b.is_national()) {
+                    if !national_broadcasts.contains(&broadcast.name) {
+                        national_broadcasts.push(broadcast.name.clone());
+                    }
+                }
+
+                let mut game = Game::new(
+                    game_pk, game_date, date, home_team, away_team, status, client, &opts,
+                );
+                game.venue = game_venue;
+                game.national_broadcasts = national_broadcasts;
+                games.push(game);
+            }
         }
 
         Ok(LazyStream {
-            opts: opts.clone(),
+            opts,
             games,
             teams,
+            progress: None,
         })
     }
 
@@ -68,10 +173,52 @@ impl LazyStream {
         }
     }
 
+    /// Report progress as each game's streams are resolved
+    pub fn on_progress(&mut self, progress: impl Fn(usize, usize) + 'static) {
+        self.progress = Some(Box::new(progress));
+    }
+
     pub fn games(&self) -> Vec<Game> {
         self.games.clone()
     }
 
+    /// Only keep games with a team matching one of the names, checked the same way as
+    /// --team
+    pub fn retain_teams(&mut self, names: &[String]) {
+        self.games
+            .retain(|game| names.iter().any(|name| game.has_team_matching(name)));
+    }
+
+    /// Only keep games broadcast nationally
+    pub fn retain_national(&mut self) {
+        self.games
+            .retain(|game| !game.national_broadcasts.is_empty());
+    }
+
+    /// Only keep the streams with one of the feed types, and the games with any left. Each
+    /// game's streams are loaded to check them, but none of their links are resolved.
+    pub async fn retain_feeds(&mut self, feeds: &[FeedType], concurrency: usize) {
+        self.resolve_streams(concurrency).await;
+
+        for game in self.games.iter_mut() {
+            if let Some(streams) = game.streams.as_mut() {
+                streams.retain(|feed_type, _| feeds.contains(feed_type));
+            }
+        }
+        // Games with streams that failed to load are kept, so they're still warned about
+        self.games.retain(|game| {
+            game.streams
+                .as_ref()
+                .map_or(true, |streams| !streams.is_empty())
+        });
+    }
+
+    /// Only keep the first count games by start time
+    pub fn truncate(&mut self, count: usize) {
+        self.games.sort_by_key(|game| game.game_date);
+        self.games.truncate(count);
+    }
+
     pub fn check_team_abbrev(&self, team_abbrev: &str) -> Result<(), Error> {
         if self
             .teams
@@ -95,8 +242,25 @@ impl LazyStream {
         }
     }
 
+    /// Get the streams for each game, without resolving any links
     #[allow(clippy::drop_ref)]
-    pub async fn resolve_with_master_link(&mut self, cdn: &Cdn) {
+    pub async fn resolve_streams(&mut self, concurrency: usize) {
+        let tasks: Vec<_> = self
+            .games
+            .iter_mut()
+            .map(|game| {
+                async {
+                    game.resolve_streams().await;
+                    drop(game);
+                }
+            })
+            .collect();
+
+        resolve_all(tasks, concurrency, &self.progress).await;
+    }
+
+    #[allow(clippy::drop_ref)]
+    pub async fn resolve_with_master_link(&mut self, cdn: &Cdn, concurrency: usize) {
         let tasks: Vec<_> = self
             .games
             .iter_mut()
@@ -108,11 +272,16 @@ impl LazyStream {
             })
             .collect();
 
-        future::join_all(tasks).await;
+        resolve_all(tasks, concurrency, &self.progress).await;
     }
 
     #[allow(clippy::drop_ref)]
-    pub async fn resolve_with_quality_link(&mut self, cdn: &Cdn, quality: &Quality) {
+    pub async fn resolve_with_quality_link(
+        &mut self,
+        cdn: &Cdn,
+        quality: &Quality,
+        concurrency: usize,
+    ) {
         let tasks: Vec<_> = self
             .games
             .iter_mut()
@@ -124,7 +293,25 @@ impl LazyStream {
             })
             .collect();
 
-        future::join_all(tasks).await;
+        resolve_all(tasks, concurrency, &self.progress).await;
+    }
+}
+
+/// Run the tasks, concurrency at a time, reporting progress as each finishes
+async fn resolve_all<F: Future<Output = ()>>(
+    tasks: Vec<F>,
+    concurrency: usize,
+    progress: &Option<Progress>,
+) {
+    let total = tasks.len();
+    let mut resolved = futures::stream::iter(tasks).buffer_unordered(concurrency.max(1));
+
+    let mut count = 0;
+    while let Some(()) = resolved.next().await {
+        count += 1;
+        if let Some(progress) = progress {
+            progress(count, total);
+        }
     }
 }
 
@@ -137,16 +324,41 @@ pub struct Game {
     pub home_team: Team,
     pub away_team: Team,
     pub game_content: Option<GameContentResponse>,
+    pub status: GameStatus,
+    /// Name of the arena the game is played in, if the schedule has it
+    pub venue: Option<String>,
+    /// Networks broadcasting the game nationally, ie. 'ESPN'
+    pub national_broadcasts: Vec<String>,
+    /// Content couldn't be fetched for the thumbnail and description, so they're skipped
+    content_failed: bool,
+    hosts: Vec<String>,
+    cdn_fallback: bool,
+    /// Fetch the master m3u8 along with each master link, to detect blackouts
+    check_blackout: bool,
+    quality_fallback: bool,
+    max_height: Option<u32>,
+    audio: bool,
+    retries: u32,
+    client: Client,
 }
 
 impl Game {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         game_pk: u64,
         game_date: DateTime<Utc>,
         selected_date: NaiveDate,
         home_team: Team,
         away_team: Team,
+        status: GameStatus,
+        client: &Client,
+        opts: &Opt,
     ) -> Self {
+        let generating = match opts.command {
+            Command::Generate { .. } => true,
+            _ => false,
+        };
+
         Game {
             game_pk,
             game_date,
@@ -155,27 +367,87 @@ impl Game {
             home_team,
             away_team,
             game_content: None,
+            status,
+            venue: None,
+            national_broadcasts: vec![],
+            content_failed: false,
+            hosts: opts.hosts(),
+            cdn_fallback: !opts.no_fallback,
+            // Needed to fall back from a blacked out CDN, and always when generating so
+            // blacked out streams are left out of the output
+            check_blackout: !opts.no_fallback || generating,
+            quality_fallback: opts.quality_fallback,
+            max_height: opts.max_quality,
+            audio: opts.audio,
+            retries: opts.retries,
+            client: client.clone(),
+        }
+    }
+
+    /// Client the game's requests are made with, to share with any related requests
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Warning if stream links likely won't play, because the game hasn't
+    /// started yet or finished a while ago
+    pub fn availability_warning(&self) -> Option<String> {
+        let now = Utc::now();
+
+        match self.status.state() {
+            GameState::Preview if self.game_date > now => Some(format!(
+                "{} @ {} hasn't started yet, the stream may not play until it does",
+                self.away_team.name, self.home_team.name
+            )),
+            GameState::Final if now - self.game_date > Duration::hours(FINAL_STREAM_HOURS) => {
+                Some(format!(
+                    "{} @ {} finished over {} hours ago, the stream may no longer play",
+                    self.away_team.name, self.home_team.name, FINAL_STREAM_HOURS
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Current status with score, ie. 'Final 3-2', if the game has started
+    pub fn status_with_score(&self) -> Option<String> {
+        match self.status.state() {
+            GameState::Preview => None,
+            _ => Some(format!(
+                "{} {}-{}",
+                self.status.detailed_state, self.status.away_score, self.status.home_score
+            )),
         }
     }
 
+    /// Case insensitive check if either team name contains name, or either team
+    /// abbreviation matches it
+    pub fn has_team_matching(&self, name: &str) -> bool {
+        let matches = |team: &Team| {
+            team.abbreviation.eq_ignore_ascii_case(name)
+                || team.name.to_lowercase().contains(&name.to_lowercase())
+        };
+        matches(&self.home_team) || matches(&self.away_team)
+    }
+
     pub async fn streams(&mut self) -> Result<HashMap<FeedType, Stream>, Error> {
         if self.streams.is_none() {
             let mut streams = HashMap::new();
             let game_content = self.game_content().await?;
+            let epg_title = if self.audio { "Audio" } else { "NHLTV" };
 
             for epg in game_content.media.epg {
-                if epg.title == "NHLTV" {
+                if epg.title == epg_title {
                     if let Some(items) = epg.items {
                         for item in items {
+                            // Skip any feed types that aren't supported yet
+                            let feed_type = match item.media_feed_type.parse::<FeedType>() {
+                                Ok(feed_type) => feed_type,
+                                Err(_) => continue,
+                            };
                             let id = item.media_playback_id;
-                            let feed_type = FeedType::from_str(item.media_feed_type.as_str())?;
-
-                            let stream = Stream::new(
-                                id,
-                                feed_type.clone(),
-                                self.game_date,
-                                self.selected_date,
-                            );
+
+                            let stream = Stream::new(id, feed_type.clone(), self);
                             streams.insert(feed_type, stream);
                         }
                     }
@@ -190,8 +462,12 @@ impl Game {
 
     pub async fn game_content(&mut self) -> Result<GameContentResponse, Error> {
         if self.game_content.is_none() {
-            let client = NhlClient::new();
-            let game_content = client.get_game_content(self.game_pk).await?;
+            debug!(
+                "Getting content for game {} from the stats api",
+                self.game_pk
+            );
+            let path = format!("game/{}/content", self.game_pk);
+            let game_content = get_stats(&self.client, &path, self.retries).await?;
             self.game_content = Some(game_content.clone());
             Ok(game_content)
         } else {
@@ -199,8 +475,38 @@ impl Game {
         }
     }
 
+    /// Content for the extras of a game, ie. its thumbnail. Only one attempt is made
+    /// within the request timeout, and a failure is remembered, so a game with content
+    /// that won't load doesn't stall building a large guide.
+    async fn optional_content(&mut self) -> Option<GameContentResponse> {
+        if self.game_content.is_some() {
+            return self.game_content.clone();
+        }
+        if self.content_failed {
+            return None;
+        }
+
+        let timeout = self.client.timeout;
+        let result = match async_std::future::timeout(timeout, self.game_content()).await {
+            Ok(result) => result,
+            Err(_) => Err(format_err!("Timed out after {}s", timeout.as_secs())),
+        };
+
+        match result {
+            Ok(game_content) => Some(game_content),
+            Err(e) => {
+                warn!(
+                    "Skipping thumbnail and description of game {}, content failed to load: {}",
+                    self.game_pk, e
+                );
+                self.content_failed = true;
+                None
+            }
+        }
+    }
+
     pub async fn game_cuts(&mut self) -> Option<GameContentArticleMediaImageCut> {
-        let game_content = self.game_content().await.ok()?;
+        let game_content = self.optional_content().await?;
 
         if let Some(items) = game_content.editorial.preview.items {
             let item = items.get(0)?;
@@ -213,7 +519,7 @@ impl Game {
     }
 
     pub async fn description(&mut self) -> Option<String> {
-        let game_content = self.game_content().await.ok()?;
+        let game_content = self.optional_content().await?;
 
         if let Some(items) = game_content.editorial.preview.items {
             let item = &items.get(0)?;
@@ -259,6 +565,13 @@ impl Game {
         }
     }
 
+    /// Clear cached game content and streams so they're fetched again
+    pub fn refresh(&mut self) {
+        self.game_content = None;
+        self.content_failed = false;
+        self.streams = None;
+    }
+
     async fn resolve_streams(&mut self) {
         let _ = self.streams().await;
     }
@@ -269,10 +582,14 @@ impl Game {
             self.resolve_streams().await;
         }
 
-        let tasks: Vec<_> = self
-            .streams
-            .as_mut()
-            .unwrap()
+        let streams = match self.streams.as_mut() {
+            Some(streams) => streams,
+            None => {
+                warn!("Skipping game {}, its streams failed to load", self.game_pk);
+                return;
+            }
+        };
+        let tasks: Vec<_> = streams
             .iter_mut()
             .map(|(_, stream)| {
                 async {
@@ -290,10 +607,14 @@ impl Game {
         if self.streams.is_none() {
             self.resolve_streams().await;
         }
-        let tasks: Vec<_> = self
-            .streams
-            .as_mut()
-            .unwrap()
+        let streams = match self.streams.as_mut() {
+            Some(streams) => streams,
+            None => {
+                warn!("Skipping game {}, its streams failed to load", self.game_pk);
+                return;
+            }
+        };
+        let tasks: Vec<_> = streams
             .iter_mut()
             .map(|(_, stream)| {
                 async {
@@ -307,40 +628,254 @@ impl Game {
     }
 }
 
+/// Schedule from the stats api. Only the fields used are deserialized, which the NHL and
+/// MLB schedules share.
+#[derive(Deserialize)]
+struct Schedule {
+    dates: Vec<ScheduleDate>,
+}
+
+#[derive(Deserialize)]
+struct ScheduleDate {
+    date: String,
+    games: Vec<ScheduledGame>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduledGame {
+    game_pk: u64,
+    game_date: String,
+    status: ScheduledStatus,
+    teams: ScheduledTeams,
+    venue: Option<ScheduledVenue>,
+    /// Only included when the schedule is hydrated with broadcasts
+    #[serde(default)]
+    broadcasts: Vec<ScheduledBroadcast>,
+    linescore: Option<ScheduledLinescore>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduledStatus {
+    detailed_state: String,
+}
+
+#[derive(Deserialize)]
+struct ScheduledTeams {
+    away: ScheduledTeam,
+    home: ScheduledTeam,
+}
+
+#[derive(Deserialize)]
+struct ScheduledTeam {
+    #[serde(default)]
+    score: u32,
+    team: ScheduledTeamId,
+}
+
+#[derive(Deserialize)]
+struct ScheduledTeamId {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct ScheduledVenue {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduledBroadcast {
+    name: String,
+    /// 'national', 'home' or 'away' for the NHL
+    #[serde(rename = "type", default)]
+    kind: String,
+    /// Set instead of the type for the MLB
+    #[serde(default)]
+    is_national: bool,
+}
+
+/// Current period and clock for the NHL, or inning and outs for the MLB
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduledLinescore {
+    current_period_ordinal: Option<String>,
+    current_period_time_remaining: Option<String>,
+    current_inning_ordinal: Option<String>,
+    inning_state: Option<String>,
+    outs: Option<u32>,
+}
+
+impl ScheduledLinescore {
+    /// How far the game has gone, ie. '2nd period, 12:34 left' or 'Top 5th, 2 outs'
+    fn progress(&self) -> Option<String> {
+        if let Some(ordinal) = &self.current_period_ordinal {
+            // Overtime and the shootout are 'OT' and 'SO'
+            let numbered = ["st", "nd", "rd", "th"]
+                .iter()
+                .any(|s| ordinal.ends_with(s));
+            let period = if numbered {
+                format!("{} period", ordinal)
+            } else {
+                ordinal.clone()
+            };
+
+            return match self.current_period_time_remaining.as_ref() {
+                Some(remaining) if remaining == "END" => Some(format!("End of {}", period)),
+                Some(remaining) if remaining.contains(':') => {
+                    Some(format!("{}, {} left", period, remaining))
+                }
+                _ => Some(period),
+            };
+        }
+
+        let inning = self.current_inning_ordinal.as_ref()?;
+        let progress = match (self.inning_state.as_ref(), self.outs) {
+            (Some(state), Some(outs)) if state == "Top" || state == "Bottom" => {
+                let plural = if outs == 1 { "" } else { "s" };
+                format!("{} {}, {} out{}", state, inning, outs, plural)
+            }
+            (Some(state), _) => format!("{} {}", state, inning),
+            (None, _) => inning.clone(),
+        };
+        Some(progress)
+    }
+}
+
+impl ScheduledBroadcast {
+    fn is_national(&self) -> bool {
+        self.is_national || self.kind == "national"
+    }
+}
+
+/// Teams from the stats api
+#[derive(Deserialize)]
+struct Teams {
+    teams: Vec<Team>,
+}
+
+#[derive(Clone)]
+pub struct GameStatus {
+    pub detailed_state: String,
+    pub away_score: String,
+    pub home_score: String,
+    /// Current period and clock from the linescore, ie. '2nd period, 12:34 left'
+    pub progress: Option<String>,
+}
+
+impl GameStatus {
+    pub fn state(&self) -> GameState {
+        if self.detailed_state.starts_with("Final") {
+            GameState::Final
+        } else if self.detailed_state.starts_with("In Progress") {
+            GameState::Live
+        } else {
+            GameState::Preview
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameState {
+    Preview,
+    Live,
+    Final,
+}
+
 #[derive(Clone)]
 #[allow(clippy::option_option)]
 pub struct Stream {
     id: String,
     pub feed_type: FeedType,
+    /// Other feeds of the game with the same link, merged into this stream
+    merged_feeds: Vec<FeedType>,
     game_date: DateTime<Utc>,
     selected_date: NaiveDate,
     master_link: Option<Option<String>>,
     master_m3u8: Option<String>,
     quality_link: Option<Option<String>>,
+    cdn: Option<Cdn>,
+    hosts: Vec<String>,
+    host: Option<String>,
+    cdn_fallback: bool,
+    check_blackout: bool,
+    quality_fallback: bool,
+    max_height: Option<u32>,
+    retries: u32,
+    client: Client,
 }
 
 impl Stream {
-    fn new(
-        id: String,
-        feed_type: FeedType,
-        game_date: DateTime<Utc>,
-        selected_date: NaiveDate,
-    ) -> Self {
+    fn new(id: String, feed_type: FeedType, game: &Game) -> Self {
         Stream {
             id,
             feed_type,
-            game_date,
-            selected_date,
+            merged_feeds: vec![],
+            game_date: game.game_date,
+            selected_date: game.selected_date,
             master_link: None,
             master_m3u8: None,
             quality_link: None,
+            cdn: None,
+            hosts: game.hosts.clone(),
+            host: None,
+            cdn_fallback: game.cdn_fallback,
+            check_blackout: game.check_blackout,
+            quality_fallback: game.quality_fallback,
+            max_height: game.max_height,
+            retries: game.retries,
+            client: game.client.clone(),
         }
     }
 
+    /// Feed type shown for the stream, including any feeds merged into it, ie. 'HOME/AWAY'
+    pub fn label(&self) -> String {
+        let mut label = self.feed_type.to_string();
+        for feed_type in &self.merged_feeds {
+            label.push('/');
+            label.push_str(&feed_type.to_string());
+        }
+        label
+    }
+
+    /// Merge in another feed of the game that has the same link
+    pub fn merge_feed(&mut self, feed_type: FeedType) {
+        self.merged_feeds.push(feed_type);
+    }
+
+    /// Forget any resolved or failed links, so the next request for a link tries again.
+    /// Used to keep checking for a stream that's listed but not being served yet.
+    pub fn reset_links(&mut self) {
+        self.master_link = None;
+        self.master_m3u8 = None;
+        self.quality_link = None;
+        self.cdn = None;
+        self.host = None;
+    }
+
+    /// The CDN the master link was resolved on, which can differ from the one
+    /// requested if fallback was used
+    pub fn cdn(&self) -> Option<&Cdn> {
+        self.cdn.as_ref()
+    }
+
+    /// The host the master link was resolved from, which can differ from the first one
+    /// if more than one was specified
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_ref().map(String::as_str)
+    }
+
+    /// Link on the host the master link was resolved from, otherwise the first host
     pub fn host_link(&self, cdn: &Cdn) -> String {
+        let host = self.host.as_ref().unwrap_or(&self.hosts[0]);
+        self.link_on_host(host, cdn)
+    }
+
+    fn link_on_host(&self, host: &str, cdn: &Cdn) -> String {
         format!(
             "{}/getM3U8.php?league=nhl&date={}&id={}&cdn={}",
-            HOST,
+            host,
             self.selected_date.format("%Y-%m-%d"),
             self.id,
             cdn,
@@ -349,8 +884,25 @@ impl Stream {
 
     pub async fn master_link(&mut self, cdn: &Cdn) -> Result<String, Error> {
         if self.master_link.is_none() {
-            match get_master_link(&self.host_link(cdn)).await {
+            let mut result = self.master_link_for_cdn(cdn).await;
+
+            if result.is_err() && self.cdn_fallback {
+                let fallback = cdn.fallback();
+                info!(
+                    "{} stream {} not available on {}, trying {}",
+                    self.feed_type, self.id, cdn, fallback
+                );
+                if let Ok(master_link) = self.master_link_for_cdn(&fallback).await {
+                    result = Ok(master_link);
+                }
+            }
+
+            match result {
                 Ok(master_link) => {
+                    info!(
+                        "Resolved {} stream {} to {}",
+                        self.feed_type, self.id, master_link
+                    );
                     self.master_link = Some(Some(master_link.clone()));
                     Ok(master_link)
                 }
@@ -368,26 +920,33 @@ impl Stream {
 
     pub async fn quality_link(&mut self, cdn: &Cdn, quality: &Quality) -> Result<String, Error> {
         if self.quality_link.is_none() {
-            if self.master_m3u8.is_none() {
-                if let Ok(master_link) = self.master_link(cdn).await {
-                    match get_master_m3u8(&master_link).await {
-                        Err(e) => {
-                            self.quality_link = Some(None);
-                            bail!(e);
-                        }
-                        Ok(master_m3u8) => {
-                            self.master_m3u8 = Some(master_m3u8);
-                        }
-                    }
-                } else {
-                    self.quality_link = Some(None);
-                    bail!("Master link not available yet");
-                }
+            if let Err(e) = self.load_master_m3u8(cdn).await {
+                self.quality_link = Some(None);
+                return Err(e);
             }
             let master_link = self.master_link.as_ref().unwrap().as_ref().unwrap();
             let master_m3u8 = self.master_m3u8.as_ref().unwrap();
 
-            if let Ok(quality_link) = get_quality_link(master_link, master_m3u8, quality) {
+            let mut result = match self.max_height {
+                Some(max_height) => get_max_quality_link(master_link, master_m3u8, max_height),
+                None => get_quality_link(master_link, master_m3u8, quality),
+            };
+
+            if result.is_err() && self.quality_fallback {
+                info!(
+                    "{} stream {} doesn't offer {}, using the nearest quality",
+                    self.feed_type,
+                    self.id,
+                    quality.arg()
+                );
+                result = match self.max_height {
+                    Some(_) => get_quality_link(master_link, master_m3u8, &Quality::Worst),
+                    None => get_nearest_quality_link(master_link, master_m3u8, quality),
+                };
+            }
+
+            if let Ok(quality_link) = result {
+                info!("Using {} quality link {}", quality.arg(), quality_link);
                 self.quality_link = Some(Some(quality_link.clone()));
                 Ok(quality_link)
             } else {
@@ -401,6 +960,75 @@ impl Stream {
         }
     }
 
+    /// The variant the quality link was resolved to, once it has been
+    pub fn quality_variant(&self) -> Option<Variant> {
+        let quality_link = self.quality_link.as_ref()?.as_ref()?;
+        let master_link = self.master_link.as_ref()?.as_ref()?;
+
+        get_variants(self.master_m3u8.as_ref()?)
+            .into_iter()
+            .find(|variant| {
+                join_variant_uri(master_link, &variant.uri).ok().as_ref() == Some(quality_link)
+            })
+    }
+
+    /// Alternate audio tracks listed in the master m3u8, ie. home and away commentary
+    pub async fn audio_tracks(&mut self, cdn: &Cdn) -> Result<Vec<AudioTrack>, Error> {
+        self.load_master_m3u8(cdn).await?;
+
+        Ok(get_audio_tracks(self.master_m3u8.as_ref().unwrap()))
+    }
+
+    /// All stream variants listed in the master m3u8
+    pub async fn variants(&mut self, cdn: &Cdn) -> Result<Vec<Variant>, Error> {
+        self.load_master_m3u8(cdn).await?;
+
+        Ok(get_variants(self.master_m3u8.as_ref().unwrap()))
+    }
+
+    /// Fetch the master m3u8 of the master link, unless it already was
+    async fn load_master_m3u8(&mut self, cdn: &Cdn) -> Result<(), Error> {
+        let master_link = self.master_link(cdn).await?;
+        if self.master_m3u8.is_none() {
+            let master_m3u8 = get_master_m3u8(&self.client, &master_link, self.retries).await?;
+            self.master_m3u8 = Some(master_m3u8);
+        }
+
+        Ok(())
+    }
+
+    /// Get the master link from a single CDN, trying each host in turn. With CDN fallback,
+    /// the master m3u8 also needs to load, so a dead CDN or blacked out stream can be
+    /// detected and the other CDN tried. Otherwise it's only fetched once it's needed.
+    async fn master_link_for_cdn(&mut self, cdn: &Cdn) -> Result<String, Error> {
+        let mut result = Err(format_err!("No hosts to get stream links from"));
+        for host in self.hosts.clone() {
+            let link = self.link_on_host(&host, cdn);
+            result = get_master_link(&self.client, &link, self.retries).await;
+
+            match &result {
+                Ok(_) => {
+                    self.host = Some(host);
+                    break;
+                }
+                Err(e) if self.hosts.len() > 1 => info!(
+                    "{} stream {} not available from {}: {}",
+                    self.feed_type, self.id, host, e
+                ),
+                Err(_) => {}
+            }
+        }
+        let master_link = result?;
+
+        if self.check_blackout {
+            let master_m3u8 = get_master_m3u8(&self.client, &master_link, self.retries).await?;
+            self.master_m3u8 = Some(master_m3u8);
+        }
+
+        self.cdn = Some(cdn.clone());
+        Ok(master_link)
+    }
+
     async fn resolve_master_link(&mut self, cdn: &Cdn) {
         let _ = self.master_link(cdn).await;
     }
@@ -408,86 +1036,1233 @@ impl Stream {
     async fn resolve_quality_link(&mut self, cdn: &Cdn, quality: &Quality) {
         let _ = self.quality_link(cdn, quality).await;
     }
-}
 
-async fn get_master_link(url: &str) -> Result<String, Error> {
-    let uri = url.parse::<http::Uri>().context("Failed to build URI")?;
-    let request = http::Request::builder()
-        .method("GET")
-        .uri(uri)
-        .body(Body::empty())
-        .unwrap();
+    /// Check the link loads with a success status
+    pub async fn verify_link(&self, link: &str) -> bool {
+        let uri = match link.parse::<http::Uri>() {
+            Ok(uri) => uri,
+            Err(_) => return false,
+        };
 
-    let client = NativeClient::default();
-    let resp = client.send(request).await?;
+        match with_retries(self.retries, || self.client.get(uri.clone())).await {
+            Ok(resp) => resp.status().is_success(),
+            Err(_) => false,
+        }
+    }
+}
 
-    let mut body = resp.into_body();
-    let mut body_text = String::new();
-    body.read_to_string(&mut body_text)
-        .await
-        .context("Failed to read response body text")?;
+/// Sends http requests, so tests can respond with fixtures instead of using the network
+trait Transport: Send + Sync {
+    fn send(
+        &self,
+        request: http::Request<Body>,
+    ) -> BoxFuture<'static, Result<http::Response<Body>, Error>>;
+}
 
-    if !&body_text[..].starts_with("https") {
-        bail!("Stream not available yet");
+impl Transport for NativeClient {
+    fn send(
+        &self,
+        request: http::Request<Body>,
+    ) -> BoxFuture<'static, Result<http::Response<Body>, Error>> {
+        HttpClient::send(self, request)
+            .map(|result| result.map_err(Error::from))
+            .boxed()
     }
+}
 
-    Ok(body_text)
+/// Clients shared by all requests, so connections are kept alive and reused rather than
+/// set up again for every request
+#[derive(Clone)]
+pub struct Client {
+    http: Arc<dyn Transport>,
+    timeout: std::time::Duration,
+    /// Extra headers for requests to the host
+    headers: Arc<Vec<(String, String)>>,
+    /// Directory the daily schedules are cached in, if any
+    cache_dir: Option<Arc<PathBuf>>,
+    /// Proxy for every request, from --proxy
+    proxy: Option<http::Uri>,
 }
 
-async fn get_master_m3u8(url: &str) -> Result<String, Error> {
-    let uri = url.parse::<http::Uri>().context("Failed to build URI")?;
-    let request = http::Request::builder()
-        .method("GET")
-        .uri(uri)
-        .body(Body::empty())
-        .unwrap();
+impl Client {
+    pub fn new(opts: &Opt) -> Self {
+        let timeout = std::time::Duration::from_secs(opts.timeout);
+        let mut client = Client::with_transport(NativeClient::default(), timeout);
+        client.headers = Arc::new(opts.headers());
+        client.cache_dir = Some(Arc::new(cache_dir()));
+        client.proxy = opts.proxy.clone();
+        client
+    }
+
+    fn with_transport(transport: impl Transport + 'static, timeout: std::time::Duration) -> Self {
+        Client {
+            http: Arc::new(transport),
+            timeout,
+            headers: Arc::new(vec![]),
+            cache_dir: None,
+            proxy: None,
+        }
+    }
 
-    let client = NativeClient::default();
-    let resp = client.send(request).await?;
+    /// Fail the request if it doesn't complete within the timeout, so a stalled
+    /// server errors and can be retried instead of hanging
+    async fn timeout<T, E: Into<Error>>(
+        &self,
+        request: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, Error> {
+        match async_std::future::timeout(self.timeout, request).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => bail!("Request timed out after {}s", self.timeout.as_secs()),
+        }
+    }
 
-    let mut body = resp.into_body();
-    let mut body_text = String::new();
-    body.read_to_string(&mut body_text)
-        .await
-        .context("Failed to read response body text")?;
+    /// Send a GET request, failing on a server error so it can be retried
+    pub(crate) async fn get(&self, uri: http::Uri) -> Result<http::Response<Body>, Error> {
+        self.send_get(uri, &[]).await
+    }
 
-    if body_text[..].starts_with("#EXTM3U") {
-        return Ok(body_text);
+    /// Send a GET request to the host, with any extra headers it needs
+    async fn get_from_host(&self, uri: http::Uri) -> Result<http::Response<Body>, Error> {
+        self.send_get(uri, &self.headers).await
     }
 
-    bail!("Failed to get master m3u8");
-}
+    async fn send_get(
+        &self,
+        uri: http::Uri,
+        headers: &[(String, String)],
+    ) -> Result<http::Response<Body>, Error> {
+        debug!("GET {}", uri);
+        let mut request = http::Request::builder();
+        request
+            .method("GET")
+            .uri(uri.clone())
+            .header("User-Agent", format!("lazystream/{}", VERSION));
+        for (name, value) in headers {
+            request.header(name.as_str(), value.as_str());
+        }
+        // Set on each request, which the native client passes along to curl
+        if let Some(proxy) = &self.proxy {
+            request.proxy(Some(proxy.clone()));
+        }
+        let request = request.body(Body::empty())?;
 
-fn get_quality_link(
-    master_link: &str,
-    master_m3u8: &str,
-    quality: &Quality,
-) -> Result<String, Error> {
-    let quality_str: &str = quality.clone().into();
-    let quality_check = format!("x{}", quality_str);
+        let resp = self.timeout(self.http.send(request)).await?;
+        debug!("{} responded with {}", uri, resp.status());
 
-    let mut quality_idx = None;
-    for (idx, line) in master_m3u8.lines().enumerate() {
-        if (quality == &Quality::_720p60 && line.contains("FRAME-RATE"))
-            || (quality != &Quality::_720p60 && line.contains(&quality_check))
-        {
-            quality_idx = Some(idx + 1);
+        if resp.status().is_server_error() {
+            bail!("Server responded with {}", resp.status());
         }
+
+        Ok(resp)
     }
+}
+
+/// Get a response from the stats api at path, ie. 'teams', and deserialize it
+async fn get_stats<T: DeserializeOwned>(
+    client: &Client,
+    path: &str,
+    retries: u32,
+) -> Result<T, Error> {
+    let body = get_stats_body(client, path, retries).await?;
+    parse_stats(path, &body)
+}
 
-    if let Some(idx) = quality_idx {
-        let quality_line = master_m3u8
-            .lines()
-            .nth(idx)
-            .ok_or_else(|| format_err!("No stream found matching quality specified"))?;
+fn parse_stats<T: DeserializeOwned>(path: &str, body: &str) -> Result<T, Error> {
+    let response = serde_json::from_str::<T>(body)
+        .context(format_err!("Failed to parse {} from the stats api", path))?;
 
-        let master_link_parts = master_link.rsplitn(2, '/').collect::<Vec<&str>>();
-        if master_link_parts.len() == 2 {
-            let quality_link = format!("{}/{}", master_link_parts[1], quality_line);
+    Ok(response)
+}
 
-            return Ok(quality_link);
-        }
+async fn get_stats_body(client: &Client, path: &str, retries: u32) -> Result<String, Error> {
+    let uri = format!("{}/{}", STATS_API, path).parse::<http::Uri>()?;
+    let resp = with_retries(retries, || client.get(uri.clone()))
+        .await
+        .context(STATS_API_OFFLINE)?;
+
+    if !resp.status().is_success() {
+        return Err(format_err!("Server responded with {}", resp.status())
+            .context(STATS_API_OFFLINE)
+            .into());
     }
 
-    bail!("No stream found matching quality specified");
+    let mut body = String::new();
+    client
+        .timeout(resp.into_body().read_to_string(&mut body))
+        .await
+        .context("Failed to read response body text")?;
+
+    Ok(body)
+}
+
+/// Schedule for the query, ie. 'date=2020-01-10' or 'gamePk=2019020702'
+async fn get_schedule(client: &Client, query: &str, retries: u32) -> Result<Schedule, Error> {
+    get_stats(client, &schedule_path(query), retries).await
+}
+
+fn schedule_path(query: &str) -> String {
+    format!("schedule?{}&hydrate={}", query, SCHEDULE_HYDRATE)
+}
+
+/// Schedule of the date, from the cache if it was fetched in the last
+/// SCHEDULE_CACHE_SECS. The raw response is cached, so it's parsed the same either way.
+async fn get_schedule_for(client: &Client, date: NaiveDate, opts: &Opt) -> Result<Schedule, Error> {
+    let path = schedule_path(&format!("date={}", date.format("%Y-%m-%d")));
+    let cache_path = client
+        .cache_dir
+        .as_ref()
+        .map(|dir| dir.join(format!("schedule-nhl-{}.json", date.format("%Y-%m-%d"))));
+
+    if let (Some(cache_path), false) = (&cache_path, opts.refresh) {
+        if let Some(body) = read_cache(cache_path).await {
+            match parse_stats(&path, &body) {
+                Ok(schedule) => {
+                    debug!("Using the schedule cached in {:?}", cache_path);
+                    return Ok(schedule);
+                }
+                Err(e) => debug!("Ignoring the schedule cached in {:?}: {}", cache_path, e),
+            }
+        }
+    }
+
+    let body = get_stats_body(client, &path, opts.retries).await?;
+    let schedule = parse_stats(&path, &body)?;
+
+    if let Some(cache_path) = cache_path {
+        if let Err(e) = write_cache(&cache_path, &body).await {
+            debug!("Failed to cache the schedule in {:?}: {}", cache_path, e);
+        }
+    }
+
+    Ok(schedule)
+}
+
+/// Contents of the cache file, if it was written within SCHEDULE_CACHE_SECS
+async fn read_cache(path: &Path) -> Option<String> {
+    let modified = fs::metadata(path).await.ok()?.modified().ok()?;
+    if modified.elapsed().ok()?.as_secs() >= SCHEDULE_CACHE_SECS {
+        return None;
+    }
+
+    fs::read_to_string(path).await.ok()
+}
+
+async fn write_cache(path: &Path, body: &str) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::write(path, body).await?;
+
+    Ok(())
+}
+
+/// The user's lazystream cache directory, or one in the temp directory if there's no
+/// home directory
+fn cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("lazystream")
+}
+
+async fn get_teams(client: &Client, retries: u32) -> Result<Vec<Team>, Error> {
+    let teams = get_stats::<Teams>(client, "teams", retries).await?;
+    Ok(teams.teams)
+}
+
+fn parse_schedule_date(date: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .context(format_err!("Invalid schedule date {}", date))
+        .map_err(Error::from)
+}
+
+async fn get_master_link(client: &Client, url: &str, retries: u32) -> Result<String, Error> {
+    let uri = url.parse::<http::Uri>().context("Failed to build URI")?;
+    let host = uri.host().unwrap_or_default().to_string();
+
+    let resp = with_retries(retries, || client.get_from_host(uri.clone()))
+        .await
+        .context(format_err!(
+            "Could not connect to {}, the host appears to be offline. \
+             A different host can be specified with --host",
+            host
+        ))?;
+
+    if !resp.status().is_success() {
+        return Err(status_error(resp.status())
+            .context("Stream not available yet")
+            .into());
+    }
+
+    let mut body = resp.into_body();
+    let mut body_text = String::new();
+    client
+        .timeout(body.read_to_string(&mut body_text))
+        .await
+        .context("Failed to read response body text")?;
+
+    if !&body_text[..].starts_with("https") {
+        bail!("Stream not available yet");
+    }
+
+    Ok(body_text)
+}
+
+async fn get_master_m3u8(client: &Client, url: &str, retries: u32) -> Result<String, Error> {
+    let uri = url.parse::<http::Uri>().context("Failed to build URI")?;
+
+    let resp = with_retries(retries, || client.get(uri.clone()))
+        .await
+        .context(format_err!(
+            "Could not connect to the CDN to get the master m3u8. \
+             A different CDN can be specified with --cdn"
+        ))?;
+
+    if !resp.status().is_success() {
+        return Err(status_error(resp.status())
+            .context("Failed to get master m3u8")
+            .into());
+    }
+
+    let mut body = resp.into_body();
+    let mut body_text = String::new();
+    client
+        .timeout(body.read_to_string(&mut body_text))
+        .await
+        .context("Failed to read response body text")?;
+
+    if !body_text[..].starts_with("#EXTM3U") {
+        bail!("Failed to get master m3u8");
+    }
+
+    if is_blackout(&body_text) {
+        bail!("Stream is blacked out");
+    }
+
+    Ok(body_text)
+}
+
+/// Download the file at url, ie. an icon image
+pub async fn download(client: &Client, url: &str, retries: u32) -> Result<Vec<u8>, Error> {
+    let uri = url.parse::<http::Uri>().context("Failed to build URI")?;
+
+    let resp = with_retries(retries, || client.get(uri.clone())).await?;
+    if !resp.status().is_success() {
+        bail!("Server responded with {}", resp.status());
+    }
+
+    let mut body = resp.into_body();
+    let mut bytes = vec![];
+    client
+        .timeout(body.read_to_end(&mut bytes))
+        .await
+        .context("Failed to read response body")?;
+
+    Ok(bytes)
+}
+
+async fn get_media_m3u8(client: &Client, url: &str, retries: u32) -> Result<String, Error> {
+    let uri = url.parse::<http::Uri>().context("Failed to build URI")?;
+
+    let resp = with_retries(retries, || client.get(uri.clone()))
+        .await
+        .context(format_err!("Could not connect to {}", url))?;
+
+    if !resp.status().is_success() {
+        return Err(status_error(resp.status())
+            .context("Failed to get m3u8 playlist")
+            .into());
+    }
+
+    let mut body = resp.into_body();
+    let mut body_text = String::new();
+    client
+        .timeout(body.read_to_string(&mut body_text))
+        .await
+        .context("Failed to read response body text")?;
+
+    if !body_text[..].starts_with("#EXTM3U") {
+        bail!("Link isn't an m3u8 playlist");
+    }
+
+    Ok(body_text)
+}
+
+/// Error for a response that wasn't successful, with what the status usually means
+fn status_error(status: http::StatusCode) -> Error {
+    let hint = match status.as_u16() {
+        403 => ", the stream may be geo restricted or blacked out",
+        404 => ", the stream may not be up yet",
+        _ => "",
+    };
+    format_err!("Server responded with {}{}", status, hint)
+}
+
+/// Blacked out streams resolve to a master m3u8 without any variants, or with only a
+/// blackout slate
+fn is_blackout(master_m3u8: &str) -> bool {
+    let variants = get_variants(master_m3u8);
+
+    variants.is_empty()
+        || variants
+            .iter()
+            .all(|variant| variant.uri.to_lowercase().contains("blackout"))
+}
+
+/// Make a request up to `attempts` times, backing off exponentially between
+/// failed attempts
+async fn with_retries<T, F, Fut>(attempts: u32, request: F) -> Result<T, Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(e) if attempt < attempts => {
+                let backoff = backoff_ms(attempt);
+                info!(
+                    "Attempt {} failed, retrying in {}ms: {}",
+                    attempt, backoff, e
+                );
+                task::sleep(std::time::Duration::from_millis(backoff)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Wait after the failed attempt, doubling each attempt up to MAX_BACKOFF_MS
+fn backoff_ms(attempt: u32) -> u64 {
+    2u64.checked_pow(attempt - 1)
+        .and_then(|factor| factor.checked_mul(500))
+        .map_or(MAX_BACKOFF_MS, |backoff| backoff.min(MAX_BACKOFF_MS))
+}
+
+fn get_quality_link(
+    master_link: &str,
+    master_m3u8: &str,
+    quality: &Quality,
+) -> Result<String, Error> {
+    let quality_line = match quality {
+        Quality::Best | Quality::Worst => {
+            let variants = get_variants(master_m3u8);
+            let variant = if quality == &Quality::Best {
+                variants.into_iter().max_by_key(|variant| variant.bandwidth)
+            } else {
+                variants.into_iter().min_by_key(|variant| variant.bandwidth)
+            };
+            variant.map(|variant| variant.uri)
+        }
+        _ => {
+            let quality_str: &str = quality.clone().into();
+            let quality_check = format!("x{}", quality_str);
+
+            let mut quality_idx = None;
+            for (idx, line) in master_m3u8.lines().enumerate() {
+                if (quality == &Quality::_720p60 && line.contains("FRAME-RATE"))
+                    || (quality != &Quality::_720p60 && line.contains(&quality_check))
+                {
+                    quality_idx = Some(idx + 1);
+                }
+            }
+
+            quality_idx
+                .and_then(|idx| master_m3u8.lines().nth(idx))
+                .map(String::from)
+        }
+    }
+    .ok_or_else(|| format_err!("No stream found matching quality specified"))?;
+
+    join_variant_uri(master_link, &quality_line)
+}
+
+/// Get the link for the variant closest to the quality specified, by resolution and then
+/// frame rate, preferring the higher bandwidth variant when tied
+fn get_nearest_quality_link(
+    master_link: &str,
+    master_m3u8: &str,
+    quality: &Quality,
+) -> Result<String, Error> {
+    let variants = get_variants(master_m3u8);
+
+    let variant = match quality.height() {
+        Some(height) => variants.into_iter().min_by_key(|variant| {
+            let distance = variant
+                .height()
+                .map(|variant_height| (i64::from(variant_height) - i64::from(height)).abs())
+                .unwrap_or(std::i64::MAX);
+            let high_frame_rate = variant.frame_rate.is_some();
+            let frame_rate_mismatch = high_frame_rate != (quality == &Quality::_720p60);
+
+            (
+                distance,
+                frame_rate_mismatch,
+                std::cmp::Reverse(variant.bandwidth),
+            )
+        }),
+        None => variants.into_iter().max_by_key(|variant| variant.bandwidth),
+    }
+    .ok_or_else(|| format_err!("No streams found in master m3u8"))?;
+
+    join_variant_uri(master_link, &variant.uri)
+}
+
+/// Highest bandwidth variant with a height at or below max_height, ie. 720 for '720p'
+fn get_max_quality_link(
+    master_link: &str,
+    master_m3u8: &str,
+    max_height: u32,
+) -> Result<String, Error> {
+    let variant = get_variants(master_m3u8)
+        .into_iter()
+        .filter(|variant| {
+            variant
+                .height()
+                .map_or(false, |height| height <= max_height)
+        })
+        .max_by_key(|variant| variant.bandwidth)
+        .ok_or_else(|| format_err!("No streams at or below {}p", max_height))?;
+
+    join_variant_uri(master_link, &variant.uri)
+}
+
+/// Variant uris can be absolute, or relative to the master link
+fn join_variant_uri(master_link: &str, uri: &str) -> Result<String, Error> {
+    let link = Url::parse(master_link)
+        .and_then(|master_link| master_link.join(uri.trim()))
+        .context("Failed to build quality link from master link")?;
+
+    Ok(link.to_string())
+}
+
+/// A stream variant listed in the master m3u8
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub bandwidth: u64,
+    pub resolution: Option<String>,
+    pub frame_rate: Option<String>,
+    pub uri: String,
+}
+
+impl Variant {
+    /// Vertical resolution, from a RESOLUTION attribute like '1280x720'
+    pub fn height(&self) -> Option<u32> {
+        self.resolution
+            .as_ref()
+            .and_then(|resolution| resolution.split('x').nth(1))
+            .and_then(|height| height.parse().ok())
+    }
+}
+
+/// Parse all #EXT-X-STREAM-INF entries and their uri from the master m3u8
+fn get_variants(master_m3u8: &str) -> Vec<Variant> {
+    let mut variants = vec![];
+
+    let mut lines = master_m3u8.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("#EXT-X-STREAM-INF:") {
+            let attributes = parse_attributes(&line["#EXT-X-STREAM-INF:".len()..]);
+
+            let uri = match lines.next() {
+                Some(uri) => uri.trim().to_string(),
+                None => break,
+            };
+
+            variants.push(Variant {
+                bandwidth: attributes
+                    .get("BANDWIDTH")
+                    .and_then(|bandwidth| bandwidth.parse().ok())
+                    .unwrap_or(0),
+                resolution: attributes.get("RESOLUTION").cloned(),
+                frame_rate: attributes.get("FRAME-RATE").cloned(),
+                uri,
+            });
+        }
+    }
+
+    variants
+}
+
+/// An alternate audio rendition listed in the master m3u8
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub default: bool,
+}
+
+/// Parse all #EXT-X-MEDIA entries with TYPE=AUDIO from the master m3u8
+fn get_audio_tracks(master_m3u8: &str) -> Vec<AudioTrack> {
+    master_m3u8
+        .lines()
+        .filter(|line| line.starts_with("#EXT-X-MEDIA:"))
+        .map(|line| parse_attributes(&line["#EXT-X-MEDIA:".len()..]))
+        .filter(|attributes| attributes.get("TYPE").map(String::as_str) == Some("AUDIO"))
+        .map(|attributes| AudioTrack {
+            group_id: attributes.get("GROUP-ID").cloned().unwrap_or_default(),
+            name: attributes.get("NAME").cloned().unwrap_or_default(),
+            language: attributes.get("LANGUAGE").cloned(),
+            default: attributes.get("DEFAULT").map(String::as_str) == Some("YES"),
+        })
+        .collect()
+}
+
+/// Summary of a media playlist, showing how much of the stream can be played
+#[derive(Debug, Clone)]
+pub struct MediaPlaylist {
+    pub link: String,
+    pub segments: usize,
+    pub target_duration: Option<u32>,
+    /// Total length of all segments, in seconds
+    pub duration: f64,
+    /// EXT-X-PLAYLIST-TYPE, 'EVENT' playlists keep every segment from the start
+    pub playlist_type: Option<String>,
+    /// No EXT-X-ENDLIST yet, so segments are still being added
+    pub live: bool,
+}
+
+/// Fetch and summarize the media playlist at link. For a master m3u8, the
+/// highest bandwidth variant is fetched.
+pub async fn inspect(client: &Client, link: &str, retries: u32) -> Result<MediaPlaylist, Error> {
+    let mut link = link.to_string();
+    let mut m3u8 = get_media_m3u8(client, &link, retries).await?;
+
+    let variant = get_variants(&m3u8)
+        .into_iter()
+        .max_by_key(|variant| variant.bandwidth);
+    if let Some(variant) = variant {
+        link = join_variant_uri(&link, &variant.uri)?;
+        m3u8 = get_media_m3u8(client, &link, retries).await?;
+    }
+
+    Ok(parse_media_playlist(link, &m3u8))
+}
+
+fn parse_media_playlist(link: String, media_m3u8: &str) -> MediaPlaylist {
+    let mut playlist = MediaPlaylist {
+        link,
+        segments: 0,
+        target_duration: None,
+        duration: 0.0,
+        playlist_type: None,
+        live: true,
+    };
+
+    for line in media_m3u8.lines() {
+        let mut tag = line.splitn(2, ':');
+        let name = tag.next().unwrap_or_default();
+        let value = tag.next().unwrap_or_default().trim();
+
+        match name {
+            "#EXTINF" => {
+                let duration = value.split(',').next().unwrap_or_default();
+                playlist.segments += 1;
+                playlist.duration += duration.parse::<f64>().unwrap_or_default();
+            }
+            "#EXT-X-TARGETDURATION" => playlist.target_duration = value.parse().ok(),
+            "#EXT-X-PLAYLIST-TYPE" => playlist.playlist_type = Some(value.to_string()),
+            "#EXT-X-ENDLIST" => playlist.live = false,
+            _ => {}
+        }
+    }
+
+    playlist
+}
+
+/// Parse an m3u8 attribute list, ie. 'BANDWIDTH=1200000,CODECS="avc1.4d401f,mp4a.40.2"'
+fn parse_attributes(list: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+
+    let mut parts = vec![];
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (idx, c) in list.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                parts.push(&list[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+
+    for part in parts {
+        let mut key_value = part.splitn(2, '=');
+        if let (Some(key), Some(value)) = (key_value.next(), key_value.next()) {
+            attributes.insert(key.trim().to_string(), value.trim_matches('"').to_string());
+        }
+    }
+
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    const HOST_LINK: &str = "http://host.example.com/getM3U8.php?league=nhl&id=1&cdn=akc";
+    const MASTER_LINK: &str = "https://cdn.example.com/nhl/2020/master.m3u8?token=abc";
+    const MASTER_M3U8: &str = include_str!("../fixtures/master.m3u8");
+    const BLACKOUT_M3U8: &str = include_str!("../fixtures/blackout.m3u8");
+    const SCHEDULE_LINK: &str =
+        "https://statsapi.web.nhl.com/api/v1/schedule?gamePk=2019020702&hydrate=broadcasts,venue,linescore";
+    const SCHEDULE_DATE_LINK: &str =
+        "https://statsapi.web.nhl.com/api/v1/schedule?date=2020-01-10&hydrate=broadcasts,venue,linescore";
+    const SCHEDULE_JSON: &str = include_str!("../fixtures/schedule.json");
+    const MLB_SCHEDULE_JSON: &str = include_str!("../fixtures/mlb_schedule.json");
+    const TEAMS_LINK: &str = "https://statsapi.web.nhl.com/api/v1/teams";
+    const TEAMS_JSON: &str = include_str!("../fixtures/teams.json");
+    const CONTENT_LINK: &str = "https://statsapi.web.nhl.com/api/v1/game/2019020702/content";
+    const CONTENT_JSON: &str = include_str!("../fixtures/content.json");
+
+    /// Responds with the fixture for each uri, or 404 if there isn't one
+    struct Fixtures(HashMap<&'static str, (u16, &'static str)>);
+
+    impl Transport for Fixtures {
+        fn send(
+            &self,
+            request: http::Request<Body>,
+        ) -> BoxFuture<'static, Result<http::Response<Body>, Error>> {
+            let uri = request.uri().to_string();
+            let (status, body) = self.0.get(uri.as_str()).cloned().unwrap_or((404, ""));
+
+            let response = http::Response::builder()
+                .status(status)
+                .body(Body::from(body.as_bytes().to_vec()))
+                .unwrap();
+            future::ready(Ok(response)).boxed()
+        }
+    }
+
+    fn client(fixtures: Vec<(&'static str, (u16, &'static str))>) -> Client {
+        let fixtures = Fixtures(fixtures.into_iter().collect());
+        Client::with_transport(fixtures, std::time::Duration::from_secs(1))
+    }
+
+    fn quality_link(quality: Quality) -> String {
+        get_quality_link(MASTER_LINK, MASTER_M3U8, &quality).unwrap()
+    }
+
+    #[test]
+    fn quality_link_relative_uri() {
+        assert_eq!(
+            quality_link(Quality::_360p),
+            "https://cdn.example.com/nhl/2020/360/index.m3u8"
+        );
+        assert_eq!(
+            quality_link(Quality::_720p60),
+            "https://cdn.example.com/nhl/hd/720p60.m3u8"
+        );
+    }
+
+    #[test]
+    fn quality_link_root_relative_uri() {
+        assert_eq!(
+            quality_link(Quality::_216p),
+            "https://cdn.example.com/abs/216.m3u8"
+        );
+    }
+
+    #[test]
+    fn quality_link_absolute_uri() {
+        assert_eq!(
+            quality_link(Quality::_540p),
+            "https://other.example.com/540.m3u8"
+        );
+        assert_eq!(
+            quality_link(Quality::Best),
+            "https://cdn.example.com/nhl/hd/720p60.m3u8"
+        );
+    }
+
+    #[test]
+    fn master_link_to_quality_link() {
+        let client = client(vec![
+            (HOST_LINK, (200, MASTER_LINK)),
+            (MASTER_LINK, (200, MASTER_M3U8)),
+        ]);
+
+        task::block_on(async {
+            let master_link = get_master_link(&client, HOST_LINK, 1).await.unwrap();
+            assert_eq!(master_link, MASTER_LINK);
+
+            let master_m3u8 = get_master_m3u8(&client, &master_link, 1).await.unwrap();
+            assert_eq!(get_variants(&master_m3u8).len(), 4);
+            assert_eq!(
+                get_quality_link(&master_link, &master_m3u8, &Quality::_360p).unwrap(),
+                "https://cdn.example.com/nhl/2020/360/index.m3u8"
+            );
+        });
+    }
+
+    #[test]
+    fn master_link_not_available() {
+        let client = client(vec![(HOST_LINK, (200, "Not available"))]);
+
+        task::block_on(async {
+            let error = get_master_link(&client, HOST_LINK, 1).await.unwrap_err();
+            assert_eq!(error.to_string(), "Stream not available yet");
+        });
+    }
+
+    #[test]
+    fn master_m3u8_blackout() {
+        let client = client(vec![
+            (MASTER_LINK, (200, BLACKOUT_M3U8)),
+            (HOST_LINK, (200, "#EXTM3U\n")),
+        ]);
+
+        task::block_on(async {
+            let error = get_master_m3u8(&client, MASTER_LINK, 1).await.unwrap_err();
+            assert_eq!(error.to_string(), "Stream is blacked out");
+            assert!(get_master_m3u8(&client, HOST_LINK, 1).await.is_err());
+        });
+    }
+
+    #[test]
+    fn master_m3u8_server_error() {
+        let client = client(vec![(MASTER_LINK, (503, ""))]);
+
+        task::block_on(async {
+            let error = get_master_m3u8(&client, MASTER_LINK, 1).await.unwrap_err();
+            assert_eq!(
+                error.find_root_cause().to_string(),
+                "Server responded with 503 Service Unavailable"
+            );
+        });
+    }
+
+    #[test]
+    fn master_m3u8_not_found() {
+        let client = client(vec![(MASTER_LINK, (404, ""))]);
+
+        task::block_on(async {
+            let error = get_master_m3u8(&client, MASTER_LINK, 1).await.unwrap_err();
+            assert_eq!(error.to_string(), "Failed to get master m3u8");
+            assert_eq!(
+                error.find_root_cause().to_string(),
+                "Server responded with 404 Not Found, the stream may not be up yet"
+            );
+        });
+    }
+
+    #[test]
+    fn audio_tracks() {
+        let master_m3u8 = "#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Home\",LANGUAGE=\"en\",DEFAULT=YES,URI=\"home.m3u8\"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Away\",LANGUAGE=\"en\",URI=\"away.m3u8\"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"English\",URI=\"subs.m3u8\"
+#EXT-X-STREAM-INF:BANDWIDTH=1200000,AUDIO=\"aac\"
+360/index.m3u8
+";
+
+        let tracks = get_audio_tracks(master_m3u8);
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].name, "Home");
+        assert!(tracks[0].default);
+        assert_eq!(tracks[1].group_id, "aac");
+        assert!(!tracks[1].default);
+    }
+
+    #[test]
+    fn media_playlist() {
+        let media_m3u8 = "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:6
+#EXT-X-PLAYLIST-TYPE:EVENT
+#EXTINF:6.000,
+segment1.ts
+#EXTINF:6.000,
+segment2.ts
+#EXTINF:4.500,
+segment3.ts
+";
+
+        let playlist = parse_media_playlist(String::from("link"), media_m3u8);
+        assert_eq!(playlist.segments, 3);
+        assert_eq!(playlist.target_duration, Some(6));
+        assert!((playlist.duration - 16.5).abs() < f64::EPSILON);
+        assert_eq!(
+            playlist.playlist_type.as_ref().map(String::as_str),
+            Some("EVENT")
+        );
+        assert!(playlist.live);
+
+        let ended = parse_media_playlist(String::from("link"), "#EXTM3U\n#EXT-X-ENDLIST\n");
+        assert!(!ended.live);
+    }
+
+    #[test]
+    fn nearest_quality_link() {
+        let nearest = |quality| get_nearest_quality_link(MASTER_LINK, MASTER_M3U8, &quality);
+
+        assert!(get_quality_link(MASTER_LINK, MASTER_M3U8, &Quality::_504p).is_err());
+        assert_eq!(
+            nearest(Quality::_504p).unwrap(),
+            "https://other.example.com/540.m3u8"
+        );
+        assert_eq!(
+            nearest(Quality::_720p).unwrap(),
+            "https://cdn.example.com/nhl/hd/720p60.m3u8"
+        );
+        assert_eq!(
+            nearest(Quality::_224p).unwrap(),
+            "https://cdn.example.com/abs/216.m3u8"
+        );
+    }
+
+    #[test]
+    fn max_quality_link() {
+        let max = |max_height| get_max_quality_link(MASTER_LINK, MASTER_M3U8, max_height);
+
+        assert_eq!(
+            max(1080).unwrap(),
+            "https://cdn.example.com/nhl/hd/720p60.m3u8"
+        );
+        assert_eq!(max(719).unwrap(), "https://other.example.com/540.m3u8");
+        assert_eq!(
+            max(360).unwrap(),
+            "https://cdn.example.com/nhl/2020/360/index.m3u8"
+        );
+        assert!(max(200).is_err());
+    }
+
+    fn opts(args: &[&str]) -> Opt {
+        let mut argv = vec!["lazystream"];
+        argv.extend_from_slice(args);
+        argv.push("json");
+        Opt::from_iter(argv)
+    }
+
+    #[test]
+    fn schedule_for_date() {
+        let client = client(vec![
+            (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+            (TEAMS_LINK, (200, TEAMS_JSON)),
+        ]);
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110"]);
+            let lazy_stream = LazyStream::with_client(&opts, client).await.unwrap();
+            let games = lazy_stream.games();
+            assert_eq!(games.len(), 1);
+
+            let game = &games[0];
+            assert_eq!(game.game_pk, 2019020702);
+            assert_eq!(game.game_date.to_rfc3339(), "2020-01-11T00:00:00+00:00");
+            assert_eq!(game.selected_date, NaiveDate::from_ymd(2020, 1, 10));
+            assert_eq!(game.away_team.abbreviation, "TOR");
+            assert_eq!(game.home_team.abbreviation, "BOS");
+            assert_eq!(game.status_with_score().unwrap(), "Final 2-5");
+            assert_eq!(game.venue.as_ref().unwrap(), "TD Garden");
+            assert_eq!(game.national_broadcasts, vec!["NBCSN"]);
+
+            let opts = self::opts(&["--date", "20200110", "--live-only"]);
+            let client = self::client(vec![
+                (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+                (TEAMS_LINK, (200, TEAMS_JSON)),
+            ]);
+            let lazy_stream = LazyStream::with_client(&opts, client).await.unwrap();
+            assert!(lazy_stream.games().is_empty());
+        });
+    }
+
+    #[test]
+    fn schedule_cache() {
+        let cache_dir = env::temp_dir().join(format!("lazystream-test-{}", std::process::id()));
+        let cached_client = |fixtures: Vec<(&'static str, (u16, &'static str))>| {
+            let mut client = client(fixtures);
+            client.cache_dir = Some(Arc::new(cache_dir.clone()));
+            client
+        };
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110", "--retries", "1"]);
+            let client = cached_client(vec![
+                (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+                (TEAMS_LINK, (200, TEAMS_JSON)),
+            ]);
+            let lazy_stream = LazyStream::with_client(&opts, client).await.unwrap();
+            assert_eq!(lazy_stream.games().len(), 1);
+            assert!(cache_dir.join("schedule-nhl-2020-01-10.json").is_file());
+
+            // The schedule is read from the cache, even though the stats api is offline
+            let client = cached_client(vec![
+                (SCHEDULE_DATE_LINK, (503, "")),
+                (TEAMS_LINK, (200, TEAMS_JSON)),
+            ]);
+            let lazy_stream = LazyStream::with_client(&opts, client.clone())
+                .await
+                .unwrap();
+            assert_eq!(lazy_stream.games()[0].game_pk, 2019020702);
+
+            let opts = self::opts(&["--date", "20200110", "--retries", "1", "--refresh"]);
+            let error = LazyStream::with_client(&opts, client).await.err().unwrap();
+            assert_eq!(error.to_string(), STATS_API_OFFLINE);
+        });
+
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn schedule_for_game() {
+        let client = client(vec![
+            (SCHEDULE_LINK, (200, SCHEDULE_JSON)),
+            (TEAMS_LINK, (200, TEAMS_JSON)),
+            (
+                "https://statsapi.web.nhl.com/api/v1/schedule?gamePk=2019020703&hydrate=broadcasts,venue,linescore",
+                (200, "{\"dates\": []}"),
+            ),
+        ]);
+
+        task::block_on(async {
+            let opts = opts(&[]);
+            let lazy_stream = LazyStream::for_game_with_client(&opts, client.clone(), 2019020702)
+                .await
+                .unwrap();
+            assert_eq!(lazy_stream.date(), NaiveDate::from_ymd(2020, 1, 10));
+            let games = lazy_stream.games();
+            assert_eq!(games.len(), 1);
+            assert_eq!(games[0].away_team.name, "Toronto Maple Leafs");
+            assert_eq!(games[0].home_team.name, "Boston Bruins");
+
+            let error = LazyStream::for_game_with_client(&opts, client, 2019020703)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(error.to_string(), "Game 2019020703 not found");
+        });
+    }
+
+    #[test]
+    fn schedule_missing_team() {
+        let client = client(vec![
+            (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+            (TEAMS_LINK, (200, "{\"teams\": []}")),
+        ]);
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110"]);
+            let error = LazyStream::with_client(&opts, client).await.err().unwrap();
+            assert_eq!(error.to_string(), "Team 6 of game 2019020702 not found");
+        });
+    }
+
+    #[test]
+    fn schedule_stats_api_offline() {
+        let client = client(vec![(SCHEDULE_DATE_LINK, (503, ""))]);
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110", "--retries", "1"]);
+            let error = LazyStream::with_client(&opts, client).await.err().unwrap();
+            assert_eq!(error.to_string(), STATS_API_OFFLINE);
+        });
+    }
+
+    #[test]
+    fn mlb_schedule() {
+        let schedule = serde_json::from_str::<Schedule>(MLB_SCHEDULE_JSON).unwrap();
+        assert_eq!(schedule.dates.len(), 1);
+        assert_eq!(schedule.dates[0].date, "2019-07-04");
+
+        let games = &schedule.dates[0].games;
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].game_pk, 567123);
+        assert_eq!(games[0].status.detailed_state, "Final");
+        assert_eq!(games[0].teams.away.team.id, 111);
+        assert_eq!(games[0].teams.home.score, 5);
+        assert_eq!(games[0].venue.as_ref().unwrap().name, "Yankee Stadium");
+        let national = games[0].broadcasts.iter().filter(|b| b.is_national());
+        assert_eq!(
+            national.map(|b| b.name.as_str()).collect::<Vec<_>>(),
+            vec!["ESPN"]
+        );
+
+        // No score before the game starts
+        assert_eq!(games[1].status.detailed_state, "Scheduled");
+        assert_eq!(games[1].teams.home.score, 0);
+    }
+
+    #[test]
+    fn linescore_progress() {
+        let progress = |linescore| {
+            serde_json::from_str::<ScheduledLinescore>(linescore)
+                .unwrap()
+                .progress()
+        };
+
+        assert_eq!(
+            progress(
+                r#"{"currentPeriod": 2, "currentPeriodOrdinal": "2nd",
+                    "currentPeriodTimeRemaining": "12:34"}"#
+            )
+            .unwrap(),
+            "2nd period, 12:34 left"
+        );
+        assert_eq!(
+            progress(
+                r#"{"currentPeriod": 1, "currentPeriodOrdinal": "1st",
+                    "currentPeriodTimeRemaining": "END"}"#
+            )
+            .unwrap(),
+            "End of 1st period"
+        );
+        assert_eq!(
+            progress(
+                r#"{"currentPeriod": 4, "currentPeriodOrdinal": "OT",
+                    "currentPeriodTimeRemaining": "03:10"}"#
+            )
+            .unwrap(),
+            "OT, 03:10 left"
+        );
+        assert_eq!(
+            progress(
+                r#"{"currentInning": 5, "currentInningOrdinal": "5th",
+                    "inningState": "Top", "outs": 1}"#
+            )
+            .unwrap(),
+            "Top 5th, 1 out"
+        );
+        assert_eq!(
+            progress(
+                r#"{"currentInning": 5, "currentInningOrdinal": "5th",
+                    "inningState": "Middle", "outs": 3}"#
+            )
+            .unwrap(),
+            "Middle 5th"
+        );
+        // Games that haven't started have no current period
+        assert!(progress(r#"{"currentPeriod": 0, "periods": []}"#).is_none());
+    }
+
+    #[test]
+    fn game_streams() {
+        let client = client(vec![
+            (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+            (TEAMS_LINK, (200, TEAMS_JSON)),
+            (CONTENT_LINK, (200, CONTENT_JSON)),
+        ]);
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110"]);
+            let lazy_stream = LazyStream::with_client(&opts, client).await.unwrap();
+            let mut game = lazy_stream.games().remove(0);
+
+            let streams = game.streams().await.unwrap();
+            let mut feed_types = streams.keys().cloned().collect::<Vec<_>>();
+            feed_types.sort();
+            assert_eq!(
+                feed_types,
+                vec![FeedType::Home, FeedType::Away, FeedType::Composite]
+            );
+            assert_eq!(streams[&FeedType::Home].id, "70634003");
+            assert_eq!(streams[&FeedType::Away].id, "70633903");
+
+            assert_eq!(
+                game.description().await.unwrap(),
+                "Marchand can reach 20 goals for fifth straight season for Boston"
+            );
+            assert_eq!(
+                game.game_cuts().await.unwrap().cut_320_180.src,
+                "https://nhl.bamcontent.com/images/photos/313449618/320x180/cut.jpg"
+            );
+        });
+    }
+
+    #[test]
+    fn retain_feeds() {
+        let fixtures = || {
+            client(vec![
+                (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+                (TEAMS_LINK, (200, TEAMS_JSON)),
+                (CONTENT_LINK, (200, CONTENT_JSON)),
+            ])
+        };
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110"]);
+            let mut lazy_stream = LazyStream::with_client(&opts, fixtures()).await.unwrap();
+            lazy_stream
+                .retain_feeds(&[FeedType::Away, FeedType::French], 1)
+                .await;
+            let streams = lazy_stream.games()[0].streams.clone().unwrap();
+            assert_eq!(streams.keys().collect::<Vec<_>>(), vec![&FeedType::Away]);
+
+            let mut lazy_stream = LazyStream::with_client(&opts, fixtures()).await.unwrap();
+            lazy_stream.retain_feeds(&[FeedType::French], 1).await;
+            assert!(lazy_stream.games().is_empty());
+        });
+    }
+
+    #[test]
+    fn generate_checks_blackout_without_fallback() {
+        let host_link =
+            "http://host.example.com/getM3U8.php?league=nhl&date=2020-01-10&id=70634003&cdn=akc";
+        let home_master_link = |args: &[&str]| {
+            let client = client(vec![
+                (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+                (TEAMS_LINK, (200, TEAMS_JSON)),
+                (CONTENT_LINK, (200, CONTENT_JSON)),
+                (host_link, (200, MASTER_LINK)),
+                (MASTER_LINK, (200, BLACKOUT_M3U8)),
+            ]);
+            let mut argv = vec![
+                "lazystream",
+                "--date",
+                "20200110",
+                "--host",
+                "http://host.example.com",
+                "--no-fallback",
+            ];
+            argv.extend_from_slice(args);
+            let opts = Opt::from_iter(argv);
+
+            task::block_on(async {
+                let lazy_stream = LazyStream::with_client(&opts, client).await.unwrap();
+                let mut game = lazy_stream.games().remove(0);
+                let mut streams = game.streams().await.unwrap();
+                let stream = streams.get_mut(&FeedType::Home).unwrap();
+                stream.master_link(&opts.cdn).await
+            })
+        };
+
+        assert!(home_master_link(&["generate", "playlist", "-"]).is_err());
+        // Without fallback the blackout is only found when a quality is picked
+        assert_eq!(home_master_link(&["json"]).unwrap(), MASTER_LINK);
+    }
+
+    #[test]
+    fn game_audio_streams() {
+        let client = client(vec![
+            (SCHEDULE_DATE_LINK, (200, SCHEDULE_JSON)),
+            (TEAMS_LINK, (200, TEAMS_JSON)),
+            (CONTENT_LINK, (200, CONTENT_JSON)),
+        ]);
+
+        task::block_on(async {
+            let opts = opts(&["--date", "20200110", "--audio"]);
+            let lazy_stream = LazyStream::with_client(&opts, client).await.unwrap();
+            let mut game = lazy_stream.games().remove(0);
+
+            let streams = game.streams().await.unwrap();
+            assert_eq!(streams.len(), 2);
+            assert_eq!(streams[&FeedType::Home].id, "70634203");
+            assert_eq!(streams[&FeedType::Away].id, "70634303");
+        });
+    }
+
+    #[test]
+    fn retry_backoff() {
+        assert_eq!(backoff_ms(1), 500);
+        assert_eq!(backoff_ms(3), 2000);
+        assert_eq!(backoff_ms(7), MAX_BACKOFF_MS);
+        assert_eq!(backoff_ms(100), MAX_BACKOFF_MS);
+    }
 }
@@ -1,10 +1,11 @@
 use crate::{
-    log_error,
+    format_time, log_error,
     opt::{CastCommand, Command, Opt, RecordCommand},
+    sanitize_filename,
     stream::{Game, LazyStream, Stream},
 };
 use async_std::{process, task};
-use chrono::Local;
+use chrono_tz::Tz;
 use failure::{bail, format_err, Error, ResultExt};
 use http::Uri;
 use std::{net::Ipv4Addr, path::PathBuf, process::Stdio, time::Duration};
@@ -26,7 +27,7 @@ async fn process(opts: Opt) -> Result<(), Error> {
              and accessible from your PATH"
         ))?;
 
-    let (game, mut stream, command, restart, proxy) = match &opts.command {
+    let (game, mut stream, command, restart) = match &opts.command {
         Command::Record { command } => process_record(&opts, command).await?,
         Command::Cast { command } => process_cast(&opts, command).await?,
         _ => bail!("Wrong command for module"),
@@ -38,7 +39,10 @@ async fn process(opts: Opt) -> Result<(), Error> {
     }
     let link = stream.master_link(&opts.cdn).await?;
 
-    task::spawn_blocking(move || streamlink(link, game, stream, command, restart, proxy)).await?;
+    let proxy = opts.proxy.clone();
+    let timezone = opts.timezone;
+    task::spawn_blocking(move || streamlink(link, game, stream, command, restart, proxy, timezone))
+        .await?;
 
     Ok(())
 }
@@ -46,25 +50,20 @@ async fn process(opts: Opt) -> Result<(), Error> {
 async fn process_record(
     opts: &Opt,
     command: &RecordCommand,
-) -> Result<(Game, Stream, StreamlinkCommand, bool, Option<Uri>), Error> {
+) -> Result<(Game, Stream, StreamlinkCommand, bool), Error> {
     match command {
-        RecordCommand::Select {
-            output,
-            restart,
-            proxy,
-        } => {
+        RecordCommand::Select { output, restart } => {
             check_output(&output)?;
             let (game, stream) = crate::select::process(opts, true).await?;
 
             let streamlink_command = StreamlinkCommand::from(command);
-            Ok((game, stream, streamlink_command, *restart, proxy.clone()))
+            Ok((game, stream, streamlink_command, *restart))
         }
         RecordCommand::Team {
             team_abbrev,
             restart,
             feed_type,
             output,
-            proxy,
         } => {
             check_output(&output)?;
 
@@ -81,7 +80,7 @@ async fn process_record(
                 println!("Using stream feed {}", stream.feed_type);
 
                 let streamlink_command = StreamlinkCommand::from(command);
-                Ok((game, stream, streamlink_command, *restart, proxy.clone()))
+                Ok((game, stream, streamlink_command, *restart))
             } else {
                 bail!("There are no games today for {}", team_abbrev);
             }
@@ -92,24 +91,23 @@ async fn process_record(
 async fn process_cast(
     opts: &Opt,
     command: &CastCommand,
-) -> Result<(Game, Stream, StreamlinkCommand, bool, Option<Uri>), Error> {
+) -> Result<(Game, Stream, StreamlinkCommand, bool), Error> {
     task::spawn_blocking(check_vlc).await.context(format_err!(
         "Could not find and run VLC. Please ensure it is installed \
          and accessible from your PATH"
     ))?;
 
     match command {
-        CastCommand::Select { restart, proxy, .. } => {
+        CastCommand::Select { restart, .. } => {
             let (game, stream) = crate::select::process(opts, true).await?;
 
             let streamlink_command = StreamlinkCommand::from(command);
-            Ok((game, stream, streamlink_command, *restart, proxy.clone()))
+            Ok((game, stream, streamlink_command, *restart))
         }
         CastCommand::Team {
             team_abbrev,
             restart,
             feed_type,
-            proxy,
             ..
         } => {
             let lazy_stream = LazyStream::new(opts).await?;
@@ -125,7 +123,7 @@ async fn process_cast(
                 println!("Using stream feed {}", stream.feed_type);
 
                 let streamlink_command = StreamlinkCommand::from(command);
-                Ok((game, stream, streamlink_command, *restart, proxy.clone()))
+                Ok((game, stream, streamlink_command, *restart))
             } else {
                 bail!("There are no games today for {}", team_abbrev);
             }
@@ -167,6 +165,7 @@ fn streamlink(
     mut command: StreamlinkCommand,
     restart: bool,
     proxy: Option<Uri>,
+    timezone: Option<Tz>,
 ) -> Result<(), Error> {
     match &command {
         StreamlinkCommand::Record { .. } => {
@@ -220,14 +219,12 @@ fn streamlink(
         StreamlinkCommand::Record { output } => {
             let filename = format!(
                 "{} {} @ {} {}.mp4",
-                game.game_date
-                    .with_timezone(&Local)
-                    .format("%Y-%m-%d %-I:%M %p"),
+                format_time(game.game_date, timezone, "%Y-%m-%d %-I:%M %p"),
                 game.away_team.name,
                 game.home_team.name,
                 stream.feed_type
             );
-            output.push(filename);
+            output.push(sanitize_filename(&filename));
 
             _arg = output.display().to_string();
 
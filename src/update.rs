@@ -0,0 +1,62 @@
+use crate::{stream::Client, VERSION};
+use async_std::{future, task};
+use colored::Colorize;
+use failure::{bail, Error};
+use futures::AsyncReadExt;
+use log::debug;
+use serde::Deserialize;
+use std::time::Duration;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/tarkah/lazystream/releases/latest";
+
+/// Warn if a newer release is available, since older versions tend to stop working when
+/// the host changes. Any failure to check is only logged.
+pub fn check(client: &Client) {
+    match task::block_on(latest_version(client)) {
+        Ok(latest) if is_newer(&latest, VERSION) => eprintln!(
+            "\n{}: lazystream {} is available, you're using {}. Older versions can stop working \
+             when the host changes, get it from https://github.com/tarkah/lazystream/releases",
+            "WARNING".yellow(),
+            latest,
+            VERSION
+        ),
+        Ok(_) => {}
+        Err(e) => debug!("Couldn't check for a newer version: {}", e),
+    }
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+async fn latest_version(client: &Client) -> Result<String, Error> {
+    let uri = LATEST_RELEASE.parse::<http::Uri>()?;
+
+    // Don't hold up the command for long if GitHub is slow
+    let body = future::timeout(Duration::from_secs(3), async {
+        let resp = client.get(uri).await?;
+        if !resp.status().is_success() {
+            bail!("GitHub responded with {}", resp.status());
+        }
+
+        let mut body = String::new();
+        resp.into_body().read_to_string(&mut body).await?;
+        Ok::<_, Error>(body)
+    })
+    .await??;
+
+    let release = serde_json::from_str::<Release>(&body)?;
+    Ok(release.tag_name.trim_start_matches('v').to_string())
+}
+
+/// Compare dotted version numbers, ie. '1.10.0' is newer than '1.9.2'
+fn is_newer(latest: &str, current: &str) -> bool {
+    let parts = |version: &str| {
+        version
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    parts(latest) > parts(current)
+}
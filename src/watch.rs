@@ -0,0 +1,47 @@
+use crate::{
+    log_error,
+    opt::{Command, Opt},
+};
+use async_std::task;
+use chrono::{DateTime, Duration, Local, NaiveTime};
+
+/// Generate the output, then keep regenerating it every day at the watch time.
+/// Failures are logged and retried on the next run, so the watch keeps going.
+pub fn run(opts: Opt) {
+    let at = match &opts.command {
+        Command::Generate { options, .. } => options.watch_at,
+        _ => NaiveTime::from_hms(6, 0, 0),
+    };
+
+    task::block_on(async {
+        loop {
+            println!(
+                "\n{}: Regenerating output...",
+                Local::now().format("%Y-%m-%d %-I:%M %p")
+            );
+            if let Err(e) = crate::generate::process(opts.clone()).await {
+                log_error(&e);
+            }
+
+            let next = next_run(at);
+            println!("Next regeneration at {}", next.format("%Y-%m-%d %-I:%M %p"));
+
+            let wait = (next - Local::now()).to_std().unwrap_or_default();
+            task::sleep(wait).await;
+        }
+    });
+}
+
+/// Next time the clock reaches at, today if it hasn't passed yet
+fn next_run(at: NaiveTime) -> DateTime<Local> {
+    let now = Local::now();
+
+    match now.date().and_time(at) {
+        Some(next) if next > now => next,
+        _ => now
+            .date()
+            .succ()
+            .and_time(at)
+            .unwrap_or_else(|| now + Duration::days(1)),
+    }
+}